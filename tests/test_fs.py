@@ -2,15 +2,21 @@
 from __future__ import absolute_import
 from __future__ import unicode_literals
 
+import io
 import os
+import tempfile
 import unittest
 import uuid
 
 import fs
+import fs.errors
+import pkg_resources
 import six
 
+from fs.opener import registry
 from fs.test import FSTestCases
 from bindings.rust.zbox import ZboxFS
+from bindings.rust.zbox.opener import ZboxOpener
 
 
 class TestMemZboxFS(FSTestCases, unittest.TestCase):
@@ -26,6 +32,90 @@ class TestMemZboxFS(FSTestCases, unittest.TestCase):
     def make_fs(self):
         return self.zbfs.makedir(six.text_type(uuid.uuid4().hex))
 
+    def test_setinfo_readonly(self):
+        self.fs.setbytes('a.bin', b'abc')
+        before = self.fs.getinfo('a.bin', namespaces=['details'])
+        self.assertEqual(before.raw['details']['_write'], [])
+        self.fs.setinfo('a.bin', {'details': {'modified': 0, 'accessed': 0}})
+        after = self.fs.getinfo('a.bin', namespaces=['details'])
+        self.assertEqual(after.raw['details']['modified'], before.raw['details']['modified'])
+        self.assertRaises(fs.errors.ResourceNotFound, self.fs.setinfo, 'missing', {})
+
+    def test_scandir_details(self):
+        self.fs.makedir('foo')
+        self.fs.setbytes('foo/a.bin', b'abc')
+        self.fs.setbytes('foo/b.bin', b'defgh')
+        infos = {i.name: i for i in self.fs.scandir('foo', ['details'])}
+        self.assertEqual(infos['a.bin'].size, 3)
+        self.assertEqual(infos['b.bin'].size, 5)
+        self.assertEqual(len(list(self.fs.scandir('foo', page=(1, 5)))), 1)
+        walked = sorted(self.fs.walk.files('/'))
+        self.assertEqual(walked, ['/foo/a.bin', '/foo/b.bin'])
+
+    def test_dotted_dirs_listed(self):
+        # Other tests work in subdirectories, so the root is checked directly
+        names = ['.batch', '.snapshots', '.sync', '.trash']
+        for name in names:
+            self.zbfs.makedir(name)
+            self.addCleanup(self.zbfs.removedir, name)
+        self.assertTrue(set(names).issubset(self.zbfs.listdir('/')))
+        self.assertTrue(set(names).issubset(i.name for i in self.zbfs.scandir('/')))
+
+    def test_copydir_inside_source(self):
+        self.fs.makedirs('src/sub')
+        self.fs.settext('src/sub/a.txt', 'a')
+        self.fs.copydir('src', 'src/sub/copy', create=True)
+        self.assertEqual(self.fs.gettext('src/sub/copy/sub/a.txt'), 'a')
+        self.assertFalse(self.fs.exists('src/sub/copy/sub/copy'))
+
+    def test_movedir_merge(self):
+        self.fs.makedirs('src/sub')
+        self.fs.makedirs('dst/sub')
+        self.fs.settext('src/sub/a.txt', 'new')
+        self.fs.settext('dst/sub/a.txt', 'old')
+        self.fs.settext('dst/sub/b.txt', 'kept')
+        self.fs.movedir('src', 'dst')
+        self.assertFalse(self.fs.exists('src'))
+        self.assertEqual(self.fs.gettext('dst/sub/a.txt'), 'new')
+        self.assertEqual(self.fs.gettext('dst/sub/b.txt'), 'kept')
+        self.assertRaises(fs.errors.ResourceNotFound, self.fs.movedir, 'dst', 'missing')
+        self.fs.movedir('dst', 'moved', create=True)
+        self.assertEqual(sorted(self.fs.listdir('moved/sub')), ['a.txt', 'b.txt'])
+
+    def test_upload_download_progress(self):
+        data = os.urandom(100000)
+        progress = []
+        self.fs.upload('a.bin', io.BytesIO(data), chunk_size=30000, progress=progress.append)
+        self.assertEqual(progress, [30000, 60000, 90000, 100000])
+        self.assertEqual(self.fs.getbytes('a.bin'), data)
+
+        # Real files are read and written through their descriptor
+        with tempfile.TemporaryFile() as tmp:
+            tmp.write(b'header')
+            self.fs.download('a.bin', tmp)
+            self.assertEqual(tmp.tell(), 6 + len(data))
+            tmp.seek(6)
+            self.fs.upload('b.bin', tmp)
+            self.assertEqual(tmp.read(), b'')
+        self.assertEqual(self.fs.getbytes('b.bin'), data)
+
+    def test_upload_download_errors(self):
+
+        class Broken(io.RawIOBase):
+            def read(self, n=-1):
+                raise KeyError('read')
+            def write(self, b):
+                raise KeyError('write')
+
+        class Greedy(io.RawIOBase):
+            def read(self, n=-1):
+                return b'x' * (n + 1)
+
+        self.fs.setbytes('a.bin', b'data')
+        self.assertRaises(KeyError, self.fs.upload, 'b.bin', Broken())
+        self.assertRaises(KeyError, self.fs.download, 'a.bin', Broken())
+        self.assertRaises(IOError, self.fs.upload, 'b.bin', Greedy())
+
 
 class TestFileZboxFS(TestMemZboxFS):
 
@@ -39,3 +129,36 @@ class TestFileZboxFS(TestMemZboxFS):
     def tearDownClass(cls):
         cls.zbfs.close()
         cls.tmp.close()
+
+
+class TestZboxOpener(unittest.TestCase):
+
+    def test_entry_point(self):
+        entry_points = {ep.name: ep for ep in pkg_resources.iter_entry_points('fs.opener')}
+        self.assertIs(entry_points['zbox'].load(), ZboxOpener)
+        self.assertIsInstance(registry.get_opener('zbox+mem'), ZboxOpener)
+
+    def test_open_mem(self):
+        os.environ['ZBOX_TEST_PWD'] = 'secret'
+        url = "zbox+mem://{}?pwd_env=ZBOX_TEST_PWD".format(uuid.uuid4().hex)
+        with fs.open_fs(url, create=True) as zbfs:
+            self.assertIsInstance(zbfs, ZboxFS)
+            zbfs.makedir('foo')
+            self.assertEqual(zbfs.geturl('/', purpose='fs'), url)
+            self.assertEqual(zbfs.geturl('/foo', purpose='fs'), url + '!/foo')
+            self.assertRaises(fs.errors.NoURL, zbfs.geturl, '/foo')
+
+    def test_open_file(self):
+        tmp = fs.open_fs("temp://")
+        self.addCleanup(tmp.close)
+        url = "zbox+file://{}?pwd=secret&create=1".format(tmp.getsyspath('/repo'))
+        with fs.open_fs(url) as zbfs:
+            zbfs.settext('a.txt', 'abc')
+        with fs.open_fs(url.replace('&create=1', '')) as zbfs:
+            self.assertEqual(zbfs.gettext('a.txt'), 'abc')
+
+    def test_url_keeps_trash(self):
+        url = "zbox+mem://{}?pwd=secret&create=1&trash=1".format(uuid.uuid4().hex)
+        with fs.open_fs(url) as zbfs:
+            self.assertIn('trash=1', zbfs.geturl('/', purpose='fs'))
+            self.assertNotIn('secret', zbfs.geturl('/', purpose='fs'))
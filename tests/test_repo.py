@@ -1,12 +1,18 @@
 # coding: utf-8
 from __future__ import absolute_import
 
+import hashlib
 import io
+import json
 import os
 import shutil
+import tarfile
 import tempfile
+import time
 import unittest
 import uuid
+import warnings
+import zipfile
 
 from bindings.rust import zbox
 
@@ -51,6 +57,626 @@ class _TestRepo(object):
         self.assertIsInstance(f, io.RawIOBase)
         self.assertIsInstance(f, io.IOBase)
 
+    def test_copytree(self):
+        self.repo.create_dir_all('/src/sub')
+        with self.repo.open('/src/a.txt', 'w') as f:
+            f.write(b'a')
+        with self.repo.open('/src/sub/b.log', 'w') as f:
+            f.write(b'b')
+
+        self.repo.copytree('/src', '/dst')
+        self.assertTrue(self.repo.is_file('/dst/a.txt'))
+        self.assertTrue(self.repo.is_file('/dst/sub/b.log'))
+        with self.repo.open('/dst/sub/b.log') as f:
+            self.assertEqual(f.read(), b'b')
+
+        self.assertRaises(Exception, self.repo.copytree, '/src', '/dst')
+        ignore = lambda d, names: {n for n in names if n.endswith('.log')}
+        self.repo.copytree('/src', '/dst2', dirs_exist_ok=True, ignore=ignore)
+        self.assertTrue(self.repo.is_file('/dst2/a.txt'))
+        self.assertFalse(self.repo.path_exists('/dst2/sub/b.log'))
+
+        self.repo.copytree('/src', '/src/copy')
+        self.assertTrue(self.repo.is_file('/src/copy/sub/b.log'))
+        self.assertFalse(self.repo.path_exists('/src/copy/copy'))
+
+        self.repo.copytree('/src', '/src/sub/deep')
+        self.assertTrue(self.repo.is_file('/src/sub/deep/sub/b.log'))
+        self.assertTrue(self.repo.is_file('/src/sub/deep/copy/a.txt'))
+        self.assertFalse(self.repo.path_exists('/src/sub/deep/sub/deep'))
+
+    def test_move(self):
+        self.repo.create_dir_all('/foo/bar')
+        self.repo.create_dir('/baz')
+        self.assertEqual(self.repo.move('/foo', '/baz'), '/baz/foo')
+        self.assertFalse(self.repo.path_exists('/foo'))
+        self.assertTrue(self.repo.is_dir('/baz/foo/bar'))
+
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'a')
+        self.assertEqual(self.repo.move('/a.txt', '/a.txt'), '/a.txt')
+        with self.repo.open('/a.txt') as f:
+            self.assertEqual(f.read(), b'a')
+
+    def test_rmtree(self):
+        self.repo.create_dir_all('/foo/bar/baz')
+        with self.repo.open('/foo/bar/x.txt', 'w') as f:
+            f.write(b'x')
+        self.repo.rmtree('/foo')
+        self.assertFalse(self.repo.path_exists('/foo'))
+
+        errors = []
+        self.repo.rmtree('/missing', onerror=lambda *args: errors.append(args))
+        self.assertEqual(len(errors), 1)
+        self.assertEqual(errors[0][1], '/missing')
+
+    def test_copy_to(self):
+        other = zbox.Repo("mem://{}".format(uuid.uuid4().hex), "other", create=True)
+        self.repo.create_dir_all('/data/sub')
+        with self.repo.open('/data/sub/a.txt', 'w') as f:
+            f.write(b'abc')
+
+        self.repo.copy_to(other, '/data', '/copy')
+        with other.open('/copy/sub/a.txt') as f:
+            self.assertEqual(f.read(), b'abc')
+        self.assertRaises(Exception, self.repo.copy_to, other, '/data', '/x', recursive=False)
+
+        self.repo.move_to(other, '/data', '/moved')
+        self.assertFalse(self.repo.path_exists('/data'))
+        self.assertTrue(other.is_file('/moved/sub/a.txt'))
+
+    def test_import_export_tree(self):
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        os.makedirs(os.path.join(host, 'in', 'sub'))
+        with open(os.path.join(host, 'in', 'sub', 'a.txt'), 'wb') as f:
+            f.write(b'abc')
+        with open(os.path.join(host, 'in', 'b.tmp'), 'wb') as f:
+            f.write(b'tmp')
+
+        report = self.repo.import_tree(os.path.join(host, 'in'), '/data', exclude=['*.tmp'])
+        self.assertEqual(report['copied'], ['/data/sub/a.txt'])
+        self.assertEqual(report['skipped'], ['/data/b.tmp'])
+        self.assertFalse(self.repo.path_exists('/data/b.tmp'))
+
+        out = os.path.join(host, 'out')
+        report = self.repo.export_tree('/data', out)
+        self.assertEqual(report['copied'], ['/data/sub/a.txt'])
+        with open(os.path.join(out, 'sub', 'a.txt'), 'rb') as f:
+            self.assertEqual(f.read(), b'abc')
+        mtime = self.repo.metadata('/data/sub/a.txt')['modified']
+        self.assertEqual(int(os.path.getmtime(os.path.join(out, 'sub', 'a.txt'))), mtime)
+
+        report = self.repo.export_tree('/data', out)
+        self.assertEqual(report['copied'], [])
+        self.assertEqual(report['skipped'], ['/data/sub/a.txt'])
+
+    @unittest.skipUnless(hasattr(os, 'symlink'), 'symbolic links not supported')
+    def test_import_tree_symlinks(self):
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        os.makedirs(os.path.join(host, 'sub'))
+        with open(os.path.join(host, 'sub', 'a.txt'), 'wb') as f:
+            f.write(b'abc')
+        os.symlink(host, os.path.join(host, 'sub', 'loop'))
+        os.symlink(os.path.join(host, 'missing'), os.path.join(host, 'dangling'))
+
+        report = self.repo.import_tree(host, '/linked', follow_symlinks=True)
+        self.assertEqual(report['copied'], ['/linked/sub/a.txt'])
+        self.assertEqual(sorted(report['skipped']), ['/linked/dangling', '/linked/sub/loop'])
+
+    def test_sync_from_host(self):
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        for name, data in [('a.txt', b'a'), ('b.txt', b'b')]:
+            with open(os.path.join(host, name), 'wb') as f:
+                f.write(data)
+
+        report = self.repo.sync_from_host(host, '/mirror')
+        self.assertEqual(sorted(report['added']), ['/mirror/a.txt', '/mirror/b.txt'])
+
+        report = self.repo.sync_from_host(host, '/mirror', checksum=True)
+        self.assertEqual(report, {'added': [], 'updated': [], 'deleted': [], 'skipped': []})
+
+        with open(os.path.join(host, 'a.txt'), 'wb') as f:
+            f.write(b'aaa')
+        os.remove(os.path.join(host, 'b.txt'))
+
+        report = self.repo.sync_from_host(host, '/mirror', delete=True, dry_run=True)
+        self.assertEqual(report['updated'], ['/mirror/a.txt'])
+        self.assertEqual(report['deleted'], ['/mirror/b.txt'])
+        self.assertTrue(self.repo.is_file('/mirror/b.txt'))
+
+        self.repo.sync_from_host(host, '/mirror', delete=True)
+        self.assertFalse(self.repo.path_exists('/mirror/b.txt'))
+        with self.repo.open('/mirror/a.txt') as f:
+            self.assertEqual(f.read(), b'aaa')
+
+        # A same-size file with an older modification time is still noticed
+        with open(os.path.join(host, 'a.txt'), 'wb') as f:
+            f.write(b'zzz')
+        os.utime(os.path.join(host, 'a.txt'), (0, 0))
+        report = self.repo.sync_from_host(host, '/mirror')
+        self.assertEqual(report['updated'], ['/mirror/a.txt'])
+        with self.repo.open('/mirror/a.txt') as f:
+            self.assertEqual(f.read(), b'zzz')
+        report = self.repo.sync_from_host(host, '/mirror')
+        self.assertEqual(report['updated'], [])
+
+        if hasattr(os, 'symlink'):
+            os.symlink(os.path.join(host, 'missing'), os.path.join(host, 'dangling'))
+            os.symlink(host, os.path.join(host, 'loop'))
+            report = self.repo.sync_from_host(host, '/mirror')
+            self.assertEqual(sorted(report['skipped']), ['/mirror/dangling', '/mirror/loop'])
+
+    def test_sync_to(self):
+        other = zbox.Repo("mem://{}".format(uuid.uuid4().hex), "other", create=True)
+        self.repo.create_dir('/data')
+        with self.repo.open('/data/a.txt', 'w') as f:
+            f.write(b'a')
+
+        report = self.repo.sync_to(other, '/data', '/replica')
+        self.assertEqual(report['added'], ['/replica/a.txt'])
+        report = self.repo.sync_to(other, '/data', '/replica')
+        self.assertEqual(report, {'added': [], 'updated': [], 'deleted': []})
+
+        with self.repo.open('/data/a.txt', 'w') as f:
+            f.write(b'abc')
+        with other.open('/replica/extra.txt', 'w') as f:
+            f.write(b'x')
+
+        report = self.repo.sync_to(other, '/data', '/replica', delete=True)
+        self.assertEqual(report['updated'], ['/replica/a.txt'])
+        self.assertEqual(report['deleted'], ['/replica/extra.txt'])
+        with other.open('/replica/a.txt') as f:
+            self.assertEqual(f.read(), b'abc')
+
+        # A new version of the replica is noticed even with the same length
+        with other.open('/replica/a.txt', 'w') as f:
+            f.write(b'xyz')
+        report = self.repo.sync_to(other, '/data', '/replica')
+        self.assertEqual(report['updated'], ['/replica/a.txt'])
+        with other.open('/replica/a.txt') as f:
+            self.assertEqual(f.read(), b'abc')
+
+        # The recorded state survives paths containing a newline
+        with self.repo.open('/data/new\nline.txt', 'w') as f:
+            f.write(b'n')
+        report = self.repo.sync_to(other, '/data', '/replica')
+        self.assertEqual(report['added'], ['/replica/new\nline.txt'])
+        report = self.repo.sync_to(other, '/data', '/replica')
+        self.assertEqual(report['added'] + report['updated'], [])
+
+    def test_clone_to(self):
+        self.repo.create_dir_all('/data/sub')
+        for content in (b'v1', b'v2'):
+            with self.repo.open('/data/sub/a.txt', 'w') as f:
+                f.write(content)
+
+        uri = "mem://{}".format(uuid.uuid4().hex)
+        clone = self.repo.clone_to(uri, "newpwd", src_dir='/data', cipher='aes', include_history=True)
+        self.assertTrue(clone.is_dir('/sub'))
+        with clone.open('/sub/a.txt') as f:
+            self.assertEqual(f.read(), b'v2')
+
+        plain = self.repo.clone_to(uri + "-plain", "newpwd", src_dir='/data')
+        self.assertGreater(
+            clone.metadata('/sub/a.txt')['curr_version'],
+            plain.metadata('/sub/a.txt')['curr_version'],
+        )
+        self.assertRaises(ValueError, self.repo.clone_to, uri + "-x", "pwd", cipher='rot13')
+
+        self.assertIsInstance(clone, zbox.Repo)
+        self.assertEqual(clone.move('/sub', '/moved'), '/moved')
+        self.assertTrue(clone.is_file('/moved/a.txt'))
+
+    def test_snapshot(self):
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'before')
+
+        self.assertEqual(self.repo.snapshot(label='first'), 'first')
+        self.assertEqual(self.repo.list_snapshots(), ['first'])
+        self.assertRaises(Exception, self.repo.snapshot, label='first')
+
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'after')
+        with self.repo.open('/b.txt', 'w') as f:
+            f.write(b'new')
+
+        report = self.repo.restore_snapshot('first', dry_run=True)
+        self.assertEqual(report['restored'], ['/a.txt'])
+        self.assertEqual(report['removed'], ['/b.txt'])
+        self.assertTrue(self.repo.is_file('/b.txt'))
+
+        report = self.repo.restore_snapshot('first')
+        self.assertEqual(report['unrestorable'], [])
+        self.assertFalse(self.repo.path_exists('/b.txt'))
+        with self.repo.open('/a.txt') as f:
+            self.assertEqual(f.read(), b'before')
+
+        self.assertRaises(Exception, self.repo.restore_snapshot, '..')
+        self.assertRaises(Exception, self.repo.restore_snapshot, '../trash')
+
+    def test_snapshot_newline_path(self):
+        with self.repo.open('/new\nline.txt', 'w') as f:
+            f.write(b'before')
+        self.repo.snapshot(label='first')
+        with self.repo.open('/new\nline.txt', 'w') as f:
+            f.write(b'after')
+
+        report = self.repo.restore_snapshot('first')
+        self.assertEqual(report['restored'], ['/new\nline.txt'])
+        with self.repo.open('/new\nline.txt') as f:
+            self.assertEqual(f.read(), b'before')
+
+    def test_snapshot_dir_hidden(self):
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'a')
+        self.repo.snapshot(label='first')
+        self.assertEqual([e['path'] for e in self.repo.read_dir('/')], ['/a.txt'])
+
+        # Only the reserved directory is hidden, not user data with similar names
+        self.repo.create_dir('/.trash')
+        self.assertEqual(sorted(e['path'] for e in self.repo.read_dir('/')), ['/.trash', '/a.txt'])
+        self.repo.remove_dir('/.trash')
+
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        self.repo.export_tree('/', host)
+        self.assertEqual(os.listdir(host), ['a.txt'])
+        report = self.repo.sync_from_host(host, '/', delete=True)
+        self.assertEqual(report['deleted'], [])
+        self.assertEqual(self.repo.list_snapshots(), ['first'])
+
+        clone = self.repo.clone_to("mem://{}".format(uuid.uuid4().hex), "pwd")
+        self.assertEqual(clone.list_snapshots(), [])
+
+    def test_batch(self):
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'a')
+        with self.repo.open('/b.txt', 'w') as f:
+            f.write(b'b')
+
+        with self.assertRaises(ZeroDivisionError):
+            with self.repo.batch() as b:
+                b.write('/a.txt', b'changed')
+                b.write('/c.txt', b'new')
+                b.remove('/b.txt')
+                b.rename('/a.txt', '/d.txt')
+                1 / 0
+
+        self.assertEqual(sorted(e['file_name'] for e in self.repo.read_dir('/')), ['a.txt', 'b.txt'])
+        with self.repo.open('/a.txt') as f:
+            self.assertEqual(f.read(), b'a')
+
+        with self.repo.batch() as b:
+            b.remove('/b.txt')
+            with b.open('/a.txt', 'w') as f:
+                f.write(b'committed')
+        self.assertEqual([e['file_name'] for e in self.repo.read_dir('/')], ['a.txt'])
+        with self.repo.open('/a.txt') as f:
+            self.assertEqual(f.read(), b'committed')
+
+        # Entries that can not be put back are reported, and kept aside
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter('always')
+            with self.assertRaises(ZeroDivisionError) as ctx:
+                with self.repo.batch() as b:
+                    b.remove('/a.txt')
+                    with self.repo.open('/a.txt', 'w') as f:
+                        f.write(b'outside')
+                    1 / 0
+        self.assertEqual(ctx.exception.rollback_failures, ['/a.txt'])
+        self.assertEqual(len(caught), 1)
+        with self.repo.open('/a.txt') as f:
+            self.assertEqual(f.read(), b'outside')
+
+    def test_changes_since(self):
+        for name in ('a.txt', 'b.txt', 'c.txt'):
+            with self.repo.open('/' + name, 'w') as f:
+                f.write(name.encode())
+        self.repo.snapshot(label='base')
+
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'changed')
+        with self.repo.open('/d.txt', 'w') as f:
+            f.write(b'new')
+        self.repo.remove_file('/b.txt')
+
+        changes = self.repo.changes_since('base')
+        self.assertEqual(changes['added'], ['/d.txt'])
+        self.assertEqual(changes['modified'], ['/a.txt'])
+        self.assertEqual(changes['removed'], ['/b.txt'])
+
+        changes = self.repo.changes_since(0)
+        self.assertEqual(sorted(changes['added']), ['/a.txt', '/c.txt', '/d.txt'])
+        self.assertEqual(self.repo.changes_since(time.time() + 3600)['added'], [])
+
+    def test_trash(self):
+        self.repo.trash = True
+        self.repo.create_dir_all('/foo/bar')
+        with self.repo.open('/foo/a.txt', 'w') as f:
+            f.write(b'a')
+
+        self.repo.remove_file('/foo/a.txt')
+        self.repo.remove_dir_all('/foo')
+        self.assertFalse(self.repo.path_exists('/foo'))
+
+        entries = self.repo.trash_list()
+        self.assertEqual([e['path'] for e in entries], ['/foo/a.txt', '/foo'])
+        self.assertEqual(self.repo.trash_restore(entries[1]['id']), '/foo')
+        self.assertEqual(self.repo.trash_restore(entries[0]['id']), '/foo/a.txt')
+        with self.repo.open('/foo/a.txt') as f:
+            self.assertEqual(f.read(), b'a')
+
+        self.repo.remove_file('/foo/a.txt')
+        self.assertEqual(self.repo.trash_empty(older_than=3600), 0)
+        self.assertEqual(self.repo.trash_empty(), 1)
+        self.assertEqual(self.repo.trash_list(), [])
+
+    def test_trash_newline_path(self):
+        self.repo.trash = True
+        with self.repo.open('/new\nline.txt', 'w') as f:
+            f.write(b'x')
+        self.repo.remove_file('/new\nline.txt')
+
+        entries = self.repo.trash_list()
+        self.assertEqual([e['path'] for e in entries], ['/new\nline.txt'])
+        self.assertEqual(self.repo.trash_restore(entries[0]['id']), '/new\nline.txt')
+        with self.repo.open('/new\nline.txt') as f:
+            self.assertEqual(f.read(), b'x')
+
+    def test_trash_other_removals(self):
+        self.repo.trash = True
+        for name in ('/a.txt', '/b.txt', '/tree/c.txt', '/mirror/d.txt'):
+            self.repo.create_dir_all(os.path.dirname(name))
+            with self.repo.open(name, 'w') as f:
+                f.write(b'x')
+
+        self.repo.move('/a.txt', '/b.txt')
+        self.repo.rmtree('/tree', onerror=lambda *args: self.fail(args))
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        self.repo.sync_from_host(host, '/mirror', delete=True)
+
+        paths = sorted(e['path'] for e in self.repo.trash_list())
+        self.assertEqual(paths, ['/b.txt', '/mirror/d.txt', '/tree'])
+
+    def test_checksum(self):
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'abc')
+
+        self.assertEqual(
+            self.repo.checksum('/a.txt'),
+            hashlib.sha256(b'abc').hexdigest(),
+        )
+        self.assertEqual(
+            self.repo.checksum('/a.txt', algorithm='md5'),
+            hashlib.md5(b'abc').hexdigest(),
+        )
+        if hasattr(hashlib, 'blake2b'):
+            self.assertEqual(
+                self.repo.checksum('/a.txt', algorithm='blake2b', cache=False),
+                hashlib.blake2b(b'abc').hexdigest(),
+            )
+        self.assertRaises(ValueError, self.repo.checksum, '/a.txt', algorithm='crc32')
+
+        version = self.repo.metadata('/a.txt')['curr_version']
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'def')
+        self.assertEqual(
+            self.repo.checksum('/a.txt', version=version),
+            hashlib.sha256(b'abc').hexdigest(),
+        )
+        self.assertEqual(
+            self.repo.checksum('/a.txt'),
+            hashlib.sha256(b'def').hexdigest(),
+        )
+
+    def test_find_duplicates(self):
+        self.repo.create_dir('/sub')
+        for path, data in [('/a', b'same'), ('/sub/b', b'same'), ('/c', b'diff'), ('/d', b'')]:
+            with self.repo.open(path, 'w') as f:
+                f.write(data)
+        with self.repo.open('/e', 'w') as f:
+            pass
+
+        self.assertEqual(self.repo.find_duplicates(), [['/a', '/sub/b']])
+        self.assertEqual(self.repo.find_duplicates(min_size=0), [['/a', '/sub/b'], ['/d', '/e']])
+        self.assertEqual(self.repo.find_duplicates(top='/sub'), [])
+
+    def test_du_and_stats(self):
+        self.repo.create_dir_all('/foo/bar')
+        with self.repo.open('/foo/a.txt', 'w') as f:
+            f.write(b'abc')
+        with self.repo.open('/foo/bar/b.txt', 'w') as f:
+            f.write(b'defgh')
+        with self.repo.open('/foo/bar/b.txt', 'w') as f:
+            f.write(b'de')
+
+        self.assertEqual(self.repo.du('/foo'), {'bytes': 5, 'files': 2, 'dirs': 1})
+        stats = self.repo.stats()
+        self.assertEqual(stats['bytes'], 5)
+        self.assertEqual(stats['dirs'], 2)
+        self.assertGreaterEqual(stats['versions'], 3)
+        if os.path.isdir(self.tempdir):
+            self.assertGreater(stats['storage_bytes'], 0)
+        else:
+            self.assertIsNone(stats['storage_bytes'])
+
+    def test_verify(self):
+        self.repo.create_dir('/foo')
+        for path in ('/foo/a.txt', '/b.txt'):
+            with self.repo.open(path, 'w') as f:
+                f.write(b'data')
+
+        seen = []
+        report = self.repo.verify(include_versions=True, progress=lambda *a: seen.append(a))
+        self.assertEqual(report, {'checked': 2, 'errors': []})
+        self.assertEqual(sorted(p for p, _, _ in seen), ['/b.txt', '/foo/a.txt'])
+        self.assertEqual(sorted(i for _, i, _ in seen), [0, 1])
+
+        report = self.repo.verify(top='/missing')
+        self.assertEqual(report['checked'], 0)
+        self.assertEqual(report['errors'][0]['path'], '/missing')
+
+    def test_manifest(self):
+        for path in ('/a.txt', '/b.txt'):
+            with self.repo.open(path, 'w') as f:
+                f.write(b'data')
+
+        manifest = self.repo.export_manifest('/', secret='secret')
+        self.assertRaises(TypeError, self.repo.export_manifest, '/')
+        entries = json.loads(manifest)['manifest']['entries']
+        self.assertEqual([e['path'] for e in entries], ['/a.txt', '/b.txt'])
+        self.assertEqual(entries[0]['sha256'], hashlib.sha256(b'data').hexdigest())
+
+        self.assertEqual(
+            self.repo.verify_manifest(manifest, 'secret'),
+            {'missing': [], 'extra': [], 'changed': []},
+        )
+        self.assertRaises(ValueError, self.repo.verify_manifest, manifest, 'wrong')
+
+        with self.repo.open('/a.txt', 'w') as f:
+            f.write(b'other')
+        self.repo.remove_file('/b.txt')
+        with self.repo.open('/c.txt', 'w') as f:
+            f.write(b'new')
+        self.assertEqual(
+            self.repo.verify_manifest(manifest, 'secret'),
+            {'missing': ['/b.txt'], 'extra': ['/c.txt'], 'changed': ['/a.txt']},
+        )
+
+    def test_compare_with_host(self):
+        host = tempfile.mkdtemp()
+        self.addCleanup(shutil.rmtree, host)
+        self.repo.create_dir('/data')
+        for name in ('same.txt', 'changed.txt', 'repo.txt'):
+            with self.repo.open('/data/' + name, 'w') as f:
+                f.write(b'abc')
+        self.repo.export_tree('/data', host)
+
+        with open(os.path.join(host, 'changed.txt'), 'wb') as f:
+            f.write(b'xyz')
+        with open(os.path.join(host, 'host.txt'), 'wb') as f:
+            f.write(b'host')
+        os.remove(os.path.join(host, 'repo.txt'))
+        os.symlink(os.path.join(host, 'missing'), os.path.join(host, 'dangling'))
+
+        expected = {
+            'repo_only': ['repo.txt'],
+            'host_only': ['host.txt'],
+            'different': ['changed.txt'],
+            'skipped': ['dangling'],
+        }
+        self.assertEqual(self.repo.compare_with_host('/data', host, by='content'), expected)
+        self.assertRaises(ValueError, self.repo.compare_with_host, '/data', host, by='magic')
+
+    def test_grep(self):
+        self.repo.create_dir('/logs')
+        with self.repo.open('/logs/a.log', 'w') as f:
+            f.write(b'INFO start\nERROR disk full\nINFO stop\n')
+        with self.repo.open('/logs/b.log', 'w') as f:
+            f.write(b'error: [x]\n')
+        with self.repo.open('/logs/c.bin', 'w') as f:
+            f.write(b'ERROR\0\x01')
+
+        self.assertEqual(
+            list(self.repo.grep('ERROR')),
+            [('/logs/a.log', 2, 'ERROR disk full')],
+        )
+        self.assertEqual(
+            sorted(self.repo.grep('error', ignore_case=True)),
+            [('/logs/a.log', 2, 'ERROR disk full'), ('/logs/b.log', 1, 'error: [x]')],
+        )
+        self.assertEqual(list(self.repo.grep('[x]', regex=False)), [('/logs/b.log', 1, 'error: [x]')])
+        self.assertEqual(len(list(self.repo.grep('INFO', max_matches=1))), 1)
+        self.assertRaises(ValueError, self.repo.grep, '(')
+
+    def test_tar(self):
+        self.repo.create_dir_all('/data/sub')
+        with self.repo.open('/data/sub/a.txt', 'w') as f:
+            f.write(b'abc')
+
+        for compression, mode in [(None, 'r:'), ('gz', 'r:gz')]:
+            buffer = io.BytesIO()
+            self.assertEqual(self.repo.export_tar('/data', buffer, compression), 1)
+            buffer.seek(0)
+            with tarfile.open(fileobj=buffer, mode=mode) as tar:
+                self.assertEqual(sorted(tar.getnames()), ['sub', 'sub/a.txt'])
+                self.assertEqual(tar.extractfile('sub/a.txt').read(), b'abc')
+
+            buffer.seek(0)
+            dst = '/imported-{}'.format(compression)
+            report = self.repo.import_tar(buffer, dst)
+            self.assertEqual(report['copied'], [dst + '/sub/a.txt'])
+            with self.repo.open(dst + '/sub/a.txt') as f:
+                self.assertEqual(f.read(), b'abc')
+
+        self.assertRaises(ValueError, self.repo.export_tar, '/data', io.BytesIO(), 'xz')
+
+    def test_tar_long_path(self):
+        deep = '/'.join(['directory-{}'.format(i) for i in range(10)])
+        self.assertGreater(len(deep), 100)
+        self.repo.create_dir_all('/data/' + deep)
+        with self.repo.open('/data/{}/a.txt'.format(deep), 'w') as f:
+            f.write(b'abc')
+
+        buffer = io.BytesIO()
+        self.assertEqual(self.repo.export_tar('/data', buffer), 1)
+        buffer.seek(0)
+        with tarfile.open(fileobj=buffer) as tar:
+            self.assertIn(deep, tar.getnames())
+            self.assertEqual(tar.extractfile(deep + '/a.txt').read(), b'abc')
+
+    def test_zip(self):
+        self.repo.create_dir_all('/data/sub')
+        with self.repo.open('/data/sub/a.txt', 'w') as f:
+            f.write(b'abc')
+
+        buffer = io.BytesIO()
+        self.assertEqual(self.repo.export_zip('/data', buffer), 1)
+        buffer.seek(0)
+        with zipfile.ZipFile(buffer) as archive:
+            self.assertEqual(sorted(archive.namelist()), ['sub/', 'sub/a.txt'])
+            self.assertEqual(archive.read('sub/a.txt'), b'abc')
+
+        source = io.BytesIO()
+        with zipfile.ZipFile(source, 'w') as archive:
+            archive.writestr('x/y.txt', b'xyz')
+            archive.writestr('../evil.txt', b'evil')
+        source.seek(0)
+
+        report = self.repo.import_zip(source, '/imported')
+        self.assertEqual(report, {'copied': ['/imported/x/y.txt'], 'skipped': ['../evil.txt']})
+        with self.repo.open('/imported/x/y.txt') as f:
+            self.assertEqual(f.read(), b'xyz')
+
+    def test_export_git_fast_import(self):
+        self.repo.create_dir('/src')
+        for content in (b'one', b'two'):
+            with self.repo.open('/src/a.txt', 'w') as f:
+                f.write(content)
+
+        out = io.BytesIO()
+        commits = self.repo.export_git_fast_import('/src', out)
+        stream = out.getvalue()
+        self.assertEqual(stream.count(b'commit refs/heads/master\n'), commits)
+        self.assertIn(b'data 3\none\n', stream)
+        self.assertIn(b'data 3\ntwo\n', stream)
+        self.assertLess(stream.index(b'one'), stream.index(b'two'))
+        self.assertIn(b'M 100644 :', stream)
+
+        for name in ('/src/"quoted".txt', '/src/new\nline.txt'):
+            with self.repo.open(name, 'w') as f:
+                f.write(b'x')
+        out = io.BytesIO()
+        self.repo.export_git_fast_import('/src', out)
+        stream = out.getvalue()
+        self.assertIn(b' "\\"quoted\\".txt"\n', stream)
+        self.assertIn(b' "new\\nline.txt"\n', stream)
+        self.assertIn(b' a.txt\n', stream)
+
 
 
 class TestDirectoryRepo(_TestRepo, unittest.TestCase):
@@ -54,19 +54,19 @@ impl<T> ::std::convert::Into<PyResult<T>> for FSError {
 }
 
 
-impl ::std::convert::Into<PyErr> for FSError {
-    fn into(self) -> PyErr {
+impl ::std::convert::From<FSError> for PyErr {
+    fn from(err: FSError) -> PyErr {
 
         use std::error::Error;
         use zbox::Error::*;
 
-        let _path = if let Some(path) = self.1 {
+        let _path = if let Some(path) = err.1 {
             path
         } else {
-            self.0.description().to_string()
+            err.0.description().to_string()
         };
 
-        match self.0 {
+        match err.0 {
 
             // RefOverflow,
             // RefUnderflow,
@@ -113,7 +113,7 @@ impl ::std::convert::Into<PyErr> for FSError {
             Encode(err) => exc::UnicodeDecodeError::new(err.description().to_string()),
             // Decode(DecodeError),
             // Var(VarError),
-            // Io(IoError),
+            Io(err) => ::pyio::to_pyerr(err),
 
             err => exc::RuntimeError::new(err.description().to_string()),
         }
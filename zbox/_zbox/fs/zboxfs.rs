@@ -1,5 +1,6 @@
-use std::collections::HashSet;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use pyo3::prelude::*;
 use pyo3::py::*;
@@ -9,31 +10,178 @@ use ::file::Mode;
 use ::fs::enums::ResourceType;
 use ::fs::errors::FSError;
 use ::fs::errors::fsexc;
+use ::pyio::{self, BorrowedFile, PyFileObject};
+use ::repo::trash;
+use ::repo::walk::is_internal;
+
+fn timestamp(time: SystemTime) -> Option<f64> {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9)
+        .ok()
+}
+
 
 #[class(subclass)]
 pub struct ZboxFS {
     repo: ::zbox::Repo,
+    trash: bool,
     token: PyToken,
 }
 
+
+impl ZboxFS {
+
+    /// Build the raw info dictionary of a resource from its metadata.
+    fn info_from_metadata(
+        &self,
+        path: &str,
+        meta: &::zbox::Metadata,
+        namespaces: &[&str],
+    ) -> PyResult<&PyDict> {
+        let py = self.token.py();
+        let info = PyDict::new(py);
+
+        // Basic namespace - always present
+        let basic = PyDict::new(py);
+        let name = path.rsplit_terminator("/").next().unwrap_or("");
+        basic.set_item("name", PyUnicode::new(py, name))?;
+        basic.set_item("is_dir", meta.is_dir())?;
+        info.set_item("basic", basic)?;
+
+        // Details namespace
+        if namespaces.contains(&"details") {
+            let details = PyDict::new(py);
+            let resource_type = if meta.is_dir() {ResourceType::Directory} else {ResourceType::File};
+            details.set_item("size", meta.len())?;
+            details.set_item("type", resource_type as i32)?;
+            details.set_item("created", timestamp(meta.created()))?;
+            details.set_item("modified", timestamp(meta.modified()))?;
+            details.set_item("accessed", py.None())?;
+            details.set_item("metadata_changed", py.None())?;
+            // Times are managed by the repository and can not be set
+            details.set_item("_write", Vec::<&str>::new())?;
+            info.set_item("details", details)?;
+        }
+
+        Ok(info)
+    }
+
+    /// Check the source and destination of a `copydir` or `movedir` call.
+    fn check_dirs(&self, src: &str, dst: &str, create: bool) -> PyResult<()> {
+        if !self.repo.path_exists(src) {
+            return fsexc::ResourceNotFound::new(src.to_owned()).into();
+        } else if !self.repo.is_dir(src) {
+            return fsexc::DirectoryExpected::new(src.to_owned()).into();
+        } else if self.repo.is_file(dst) {
+            return fsexc::DirectoryExpected::new(dst.to_owned()).into();
+        } else if !self.repo.is_dir(dst) {
+            let has_parent = Path::new(dst).parent().map_or(true, |p| self.repo.is_dir(p));
+            if !create || !has_parent {
+                return fsexc::ResourceNotFound::new(dst.to_owned()).into();
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the content of the `src` directory into the `dst` directory.
+    ///
+    /// `top` is the destination of the whole copy, and is never copied into
+    /// itself when it lies somewhere inside of `src`.
+    fn copy_contents(&mut self, src: &Path, dst: &Path, top: &Path) -> ::zbox::Result<()> {
+        for entry in self.repo.read_dir(src)? {
+            let target = dst.join(entry.file_name());
+            if entry.path() == top || is_internal(entry.path()) || is_internal(&target) {
+                continue;
+            } else if entry.metadata().is_dir() {
+                if !self.repo.is_dir(&target) {
+                    self.repo.create_dir(&target)?;
+                }
+                self.copy_contents(entry.path(), &target, top)?;
+            } else {
+                self.repo.copy(entry.path(), &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the content of the `src` directory into `dst`, then remove `src`.
+    fn move_contents(&mut self, src: &Path, dst: &Path) -> ::zbox::Result<()> {
+        for entry in self.repo.read_dir(src)? {
+            let target = dst.join(entry.file_name());
+            if entry.metadata().is_dir() && self.repo.is_dir(&target) {
+                self.move_contents(entry.path(), &target)?;
+                continue;
+            } else if entry.metadata().is_file() && self.repo.is_file(&target) {
+                self.repo.remove_file(&target)?;
+            }
+            self.repo.rename(entry.path(), &target)?;
+        }
+        self.repo.remove_dir(src)
+    }
+}
+
+
 #[methods]
 impl ZboxFS {
 
     #[new]
-    #[args(pwd = "\"\"", create = "true")]
-    fn __new__(obj: &PyRawObject, uri: &str, pwd: &str, create: bool) -> PyResult<()> {
+    #[args(pwd = "\"\"", create = "true", trash = "false")]
+    fn __new__(obj: &PyRawObject, uri: &str, pwd: &str, create: bool, trash: bool) -> PyResult<()> {
         match ::zbox::RepoOpener::new().create(create).open(uri, pwd) {
-            Ok(repo) => obj.init(|token| ZboxFS { repo, token }),
+            Ok(repo) => obj.init(|token| ZboxFS { repo, trash, token }),
             Err(err) => FSError::from(err).into(),
         }
     }
 
     #[args(overwrite = "false")]
     fn copy(&mut self, src: &str, dst: &str, overwrite: bool) -> PyResult<()> {
-        if !overwrite && self.repo.is_file(dst) {
-            fsexc::DestinationExists::new(dst.to_owned()).into()
-        } else {
-            self.repo.copy(src, dst).map_err(|err| FSError::with_path(err, src).into())
+        if self.repo.is_dir(src) {
+            return fsexc::FileExpected::new(src.to_owned()).into();
+        } else if !self.repo.is_file(src) {
+            return fsexc::ResourceNotFound::new(src.to_owned()).into();
+        } else if !overwrite && self.repo.path_exists(dst) {
+            return fsexc::DestinationExists::new(dst.to_owned()).into();
+        }
+        match Path::new(dst).parent() {
+            Some(parent) if !self.repo.is_dir(parent) => {
+                fsexc::ResourceNotFound::new(dst.to_owned()).into()
+            }
+            _ => self.repo.copy(src, dst).map_err(|err| FSError::with_path(err, dst).into()),
+        }
+    }
+
+    #[args(create = "false")]
+    fn copydir(&mut self, src: &str, dst: &str, create: bool) -> PyResult<()> {
+        self.check_dirs(src, dst, create)?;
+        if !self.repo.is_dir(dst) {
+            self.repo.create_dir(dst).map_err(|err| FSError::with_path(err, dst))?;
+        }
+        self.copy_contents(Path::new(src), Path::new(dst), Path::new(dst))
+            .map_err(|err| FSError::with_path(err, src).into())
+    }
+
+    #[args(chunk_size = "None", progress = "None")]
+    fn download(
+        &mut self,
+        path: &str,
+        file: PyObject,
+        chunk_size: Option<usize>,
+        progress: Option<PyObject>,
+    ) -> PyResult<()> {
+        let py = self.token.py();
+        let chunk_size = chunk_size.unwrap_or(pyio::CHUNK_SIZE);
+        let mut source = self.repo.open_file(path).map_err(|err| FSError::with_path(err, path))?;
+
+        match BorrowedFile::from_object(py, &file) {
+            Some(mut host) => {
+                pyio::copy_chunks(py, &mut source, &mut host, chunk_size, &progress)?;
+                host.sync(py, &file)
+            }
+            None => {
+                let mut target = PyFileObject::new(file);
+                pyio::copy_chunks(py, &mut source, &mut target, chunk_size, &progress)?;
+                target.flush().map_err(pyio::to_pyerr)
+            }
         }
     }
 
@@ -51,33 +199,12 @@ impl ZboxFS {
 
     // FIXME: wait for PyO3/pyo3#141 and replace `PyString` with `PyUnicode`
     fn getinfo(&self, path: &str, namespaces: Option<Vec<&str>>) -> PyResult<&PyDict> {
-
         let meta = match self.repo.metadata(path) {
             Err(err) => return FSError::with_path(err, path).into(),
             Ok(meta) => meta,
         };
-
         let ns = namespaces.unwrap_or(vec!["basic"]);
-        let info = PyDict::new(self.token.py());
-        let is_dir = self.repo.is_dir(path);
-
-        // Basic namespace - always present
-        let basic = PyDict::new(self.token.py());
-        let name = path.rsplit_terminator("/").next().unwrap_or("");
-        basic.set_item("name", PyUnicode::new(self.token.py(), name));
-        basic.set_item("is_dir", is_dir);
-        info.set_item("basic", basic);
-
-        // Details namespace
-        if ns.contains(&"details") {
-            let details = PyDict::new(self.token.py());
-            let resource_type = if is_dir {ResourceType::Directory} else {ResourceType::File};
-            details.set_item("size", meta.len());
-            details.set_item("type", resource_type as i32);
-            info.set_item("details", details);
-        }
-
-        Ok(info)
+        self.info_from_metadata(path, &meta, &ns)
     }
 
     // FIXME: wait for PyO3/pyo3#141 and replace `PyString` with `PyUnicode`
@@ -85,40 +212,70 @@ impl ZboxFS {
         match self.repo.read_dir(path) {
             Err(err) => FSError::with_path(err, path).into(),
             Ok(entries) => {
-                let names = entries.iter().map(|ref e| e.file_name());
+                let visible = entries.iter().filter(|e| !is_internal(e.path()));
+                let names = visible.map(|ref e| e.file_name());
                 let strings = names.map(|ref n| PyUnicode::new(self.token.py(), n));
                 Ok(strings.collect())
             }
         }
     }
 
+    // Permissions can not be stored by the repository, so they are ignored,
+    // the same way `getinfo` reports no writable namespace to `setinfo`
     #[args(recreate = "false")]
     fn makedir(
         &mut self,
         path: &str,
-        permissions: Option<PyObject>,
+        _permissions: Option<PyObject>,
         recreate: bool,
     ) -> PyResult<()> {
         use ::zbox::Error::AlreadyExists;
         match self.repo.create_dir(path) {
-            Ok(()) => Ok(()),
-            Err(AlreadyExists) if recreate => Ok(()),
-            Err(AlreadyExists) if !recreate => fsexc::DirectoryExists::new(path.to_owned()).into(),
-            Err(err) => FSError::with_path(err, path).into(),
+            Ok(()) => (),
+            Err(AlreadyExists) if recreate && self.repo.is_dir(path) => return Ok(()),
+            Err(AlreadyExists) => return fsexc::DirectoryExists::new(path.to_owned()).into(),
+            Err(err) => return FSError::with_path(err, path).into(),
         }
+        Ok(())
     }
 
     #[args(overwrite = "false")]
     fn move_(&mut self, src: &str, dst: &str, overwrite: bool) -> PyResult<()> {
+        // `FS.move` only moves files, directories go through `FS.movedir`
         if self.repo.is_dir(src) {
             return fsexc::FileExpected::new(src.to_owned()).into();
-        }
-        if self.repo.is_file(dst) && !overwrite {
+        } else if !self.repo.is_file(src) {
+            return fsexc::ResourceNotFound::new(src.to_owned()).into();
+        } else if self.repo.is_file(dst) && !overwrite {
             return fsexc::DestinationExists::new(dst.to_owned()).into();
+        } else if src == dst {
+            return Ok(());
+        }
+        if let Some(parent) = Path::new(dst).parent() {
+            if !self.repo.is_dir(parent) {
+                return fsexc::ResourceNotFound::new(dst.to_owned()).into();
+            }
         }
+
         self.repo.rename(src, dst).map_err(|err| FSError::with_path(err, src).into())
     }
 
+    #[args(create = "false")]
+    fn movedir(&mut self, src: &str, dst: &str, create: bool) -> PyResult<()> {
+        self.check_dirs(src, dst, create)?;
+        if src == dst {
+            return Ok(());
+        }
+
+        // A missing destination lets the whole directory be renamed at once
+        let result = if self.repo.is_dir(dst) {
+            self.move_contents(Path::new(src), Path::new(dst))
+        } else {
+            self.repo.rename(src, dst)
+        };
+        result.map_err(|err| FSError::with_path(err, src).into())
+    }
+
     #[args(mode = "\"rb\"", buffering = "-1", options = "**")]
     fn openbin(
         &mut self,
@@ -149,18 +306,128 @@ impl ZboxFS {
     }
 
     fn remove(&mut self, path: &str) -> PyResult<()> {
-        self.repo
-            .remove_file(path)
-            .map_err(|err| FSError::with_path(err, path).into())
+        if self.trash && self.repo.is_file(path) {
+            trash::move_to_trash(&mut self.repo, Path::new(path))
+                .map_err(|err| FSError::with_path(err, path))?;
+        } else {
+            self.repo
+                .remove_file(path)
+                .map_err(|err| FSError::with_path(err, path))?;
+        }
+        Ok(())
     }
 
     fn removedir(&mut self, path: &str) -> PyResult<()> {
-        self.repo
-            .remove_dir(path)
-            .map_err(|err| FSError::with_path(err, path).into())
+        if self.trash && self.repo.read_dir(path).map(|e| e.is_empty()).unwrap_or(false) {
+            trash::move_to_trash(&mut self.repo, Path::new(path))
+                .map_err(|err| FSError::with_path(err, path))?;
+        } else {
+            self.repo
+                .remove_dir(path)
+                .map_err(|err| FSError::with_path(err, path))?;
+        }
+        Ok(())
     }
 
-    fn setinfo(&self, path: &str, info: &PyDict) -> PyResult<()> {
-        self.getinfo(path, None).map(|_| ())
+    fn removetree(&mut self, path: &str) -> PyResult<()> {
+        if !self.repo.path_exists(path) {
+            return fsexc::ResourceNotFound::new(path.to_owned()).into();
+        } else if !self.repo.is_dir(path) {
+            return fsexc::DirectoryExpected::new(path.to_owned()).into();
+        }
+
+        // The root can only be emptied, and keeps the internal directories
+        let targets: Vec<PathBuf> = if Path::new(path) == Path::new("/") {
+            self.repo
+                .read_dir(path)
+                .map_err(|err| FSError::with_path(err, path))?
+                .iter()
+                .map(|e| e.path().to_path_buf())
+                .filter(|p| !is_internal(p))
+                .collect()
+        } else {
+            vec![PathBuf::from(path)]
+        };
+
+        for target in targets {
+            let result = if self.trash {
+                trash::move_to_trash(&mut self.repo, &target).map(|_| ())
+            } else if self.repo.is_dir(&target) {
+                self.repo.remove_dir_all(&target)
+            } else {
+                self.repo.remove_file(&target)
+            };
+            result.map_err(|err| FSError::with_path(err, target.to_string_lossy().into_owned()))?;
+        }
+
+        Ok(())
+    }
+
+    #[args(namespaces = "None", page = "None")]
+    fn scandir(
+        &self,
+        path: &str,
+        namespaces: Option<Vec<&str>>,
+        page: Option<(usize, usize)>,
+    ) -> PyResult<Vec<&PyDict>> {
+        let mut entries = match self.repo.read_dir(path) {
+            Err(err) => return FSError::with_path(err, path).into(),
+            Ok(entries) => entries,
+        };
+        entries.retain(|e| !is_internal(e.path()));
+
+        // Slice before building infos so only the requested page is converted
+        if let Some((start, end)) = page {
+            let end = end.min(entries.len());
+            let start = start.min(end);
+            entries = entries.drain(start..end).collect();
+        }
+
+        let ns = namespaces.unwrap_or(vec!["basic"]);
+        entries
+            .iter()
+            .map(|e| self.info_from_metadata(&e.path().to_string_lossy(), &e.metadata(), &ns))
+            .collect()
+    }
+
+    fn setinfo(&self, path: &str, _info: &PyDict) -> PyResult<()> {
+        // Nothing is writable, see the `_write` lists reported by `getinfo`
+        if !self.repo.path_exists(path) {
+            return fsexc::ResourceNotFound::new(path.to_owned()).into();
+        }
+        Ok(())
+    }
+
+    #[args(chunk_size = "None", progress = "None")]
+    fn upload(
+        &mut self,
+        path: &str,
+        file: PyObject,
+        chunk_size: Option<usize>,
+        progress: Option<PyObject>,
+    ) -> PyResult<()> {
+        let py = self.token.py();
+        let chunk_size = chunk_size.unwrap_or(pyio::CHUNK_SIZE);
+        let mut target = ::zbox::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&mut self.repo, path)
+            .map_err(|err| FSError::with_path(err, path))?;
+
+        match BorrowedFile::from_object(py, &file) {
+            Some(mut host) => {
+                pyio::copy_chunks(py, &mut host, &mut target, chunk_size, &progress)?;
+                host.sync(py, &file)?;
+            }
+            None => {
+                let mut source = PyFileObject::new(file);
+                pyio::copy_chunks(py, &mut source, &mut target, chunk_size, &progress)?;
+            }
+        }
+
+        // Finishing the file encrypts and commits the last chunk
+        py.allow_threads(|| target.finish())
+            .map_err(|err| FSError::with_path(err, path).into())
     }
 }
@@ -5,11 +5,27 @@
 extern crate pyo3;
 #[macro_use]
 extern crate lazy_static;
+extern crate blake2;
+extern crate digest;
+extern crate filetime;
+extern crate flate2;
+extern crate glob;
+extern crate hmac;
+extern crate md5;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate tar;
 extern crate zbox;
+extern crate zip;
 
 mod constants;
 mod file;
 mod fs;
+mod pyio;
 mod repo;
 mod utils;
 
@@ -31,6 +47,8 @@ fn init_mod(_py: Python, m: &PyModule) -> PyResult<()> {
     zbox::init_env();
 
     m.add_class::<repo::Repo>()?;
+    m.add_class::<repo::Batch>()?;
+    m.add_class::<repo::Matches>()?;
     m.add_class::<file::File>()?;
     m.add_class::<fs::ZboxFS>()?;
 
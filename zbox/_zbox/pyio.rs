@@ -0,0 +1,233 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem::ManuallyDrop;
+
+use pyo3::prelude::*;
+use pyo3::py::*;
+
+
+/// A Python exception carried through an `io::Error`.
+struct PythonError(PyErr);
+
+// The exception is only created and taken back while holding the GIL
+unsafe impl Send for PythonError {}
+unsafe impl Sync for PythonError {}
+
+impl ::std::fmt::Debug for PythonError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "PythonError({:?})", self.0)
+    }
+}
+
+impl ::std::fmt::Display for PythonError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl ::std::error::Error for PythonError {
+    fn description(&self) -> &str {
+        "Python exception"
+    }
+}
+
+fn to_io_error(err: PyErr) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, PythonError(err))
+}
+
+/// Convert an `io::Error` to a Python exception, restoring the original
+/// exception if the error was raised by a Python file object.
+pub fn to_pyerr(err: io::Error) -> PyErr {
+    if !err.get_ref().map_or(false, |inner| inner.is::<PythonError>()) {
+        return PyErr::from(err);
+    }
+    match err.into_inner().map(|inner| inner.downcast::<PythonError>()) {
+        Some(Ok(inner)) => inner.0,
+        _ => unreachable!(),
+    }
+}
+
+
+/// An adapter exposing a Python binary file object as a Rust reader and writer.
+pub struct PyFileObject {
+    obj: PyObject,
+}
+
+impl PyFileObject {
+    pub fn new(obj: PyObject) -> Self {
+        PyFileObject { obj }
+    }
+}
+
+impl Read for PyFileObject {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let data = self.obj
+            .call_method(py, "read", (buf.len(),), None)
+            .map_err(to_io_error)?;
+        let bytes: &PyBytes = data.as_ref(py).extract().map_err(to_io_error)?;
+        let data = bytes.data();
+        if data.len() > buf.len() {
+            let msg = format!("read() returned {} bytes, {} requested", data.len(), buf.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        buf[..data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+impl Write for PyFileObject {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let written = self.obj
+            .call_method(py, "write", (PyBytes::new(py, buf),), None)
+            .map_err(to_io_error)?;
+        // Raw file objects may write less than asked, buffered ones return None
+        Ok(written.extract::<Option<usize>>(py).map_err(to_io_error)?.unwrap_or(buf.len()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        self.obj.call_method(py, "flush", (), None).map_err(to_io_error)?;
+        Ok(())
+    }
+}
+
+impl Seek for PyFileObject {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        use ::constants::io::{SEEK_CUR, SEEK_END, SEEK_SET};
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let (offset, whence) = match pos {
+            SeekFrom::Start(n) => (n as i64, *SEEK_SET),
+            SeekFrom::Current(n) => (n, *SEEK_CUR),
+            SeekFrom::End(n) => (n, *SEEK_END),
+        };
+        self.obj
+            .call_method(py, "seek", (offset, whence), None)
+            .and_then(|pos| pos.extract::<u64>(py))
+            .map_err(to_io_error)
+    }
+}
+
+
+/// A host file sharing the descriptor of a Python file object.
+///
+/// The descriptor stays owned by the Python object, so it is never closed here.
+pub struct BorrowedFile(ManuallyDrop<::std::fs::File>);
+
+impl BorrowedFile {
+    /// Borrow the descriptor of `obj`, positioned where `obj` logically is.
+    ///
+    /// Returns `None` for objects without a descriptor or without a known
+    /// position, such as in-memory buffers, pipes and sockets.
+    #[cfg(unix)]
+    pub fn from_object(py: Python, obj: &PyObject) -> Option<Self> {
+        use std::os::unix::io::FromRawFd;
+
+        // Buffered objects may hold pending writes, or have read ahead
+        obj.call_method(py, "flush", (), None).ok()?;
+        let fd = obj.call_method(py, "fileno", (), None).ok()?.extract::<i32>(py).ok()?;
+        let pos = obj.call_method(py, "tell", (), None).ok()?.extract::<u64>(py).ok()?;
+
+        let mut file = BorrowedFile(ManuallyDrop::new(unsafe { ::std::fs::File::from_raw_fd(fd) }));
+        file.0.seek(SeekFrom::Start(pos)).ok()?;
+        Some(file)
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_object(_py: Python, _obj: &PyObject) -> Option<Self> {
+        None
+    }
+
+    /// Move `obj` to the current position of the descriptor, dropping its buffer.
+    pub fn sync(&mut self, py: Python, obj: &PyObject) -> PyResult<()> {
+        let pos = self.0.seek(SeekFrom::Current(0))?;
+        obj.call_method(py, "seek", (pos,), None)?;
+        Ok(())
+    }
+}
+
+impl Read for BorrowedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for BorrowedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+
+/// Size of the chunks copied at once by `copy_chunks`.
+pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+
+/// Copy `reader` into `writer` by chunks of `chunk_size` bytes.
+///
+/// Each chunk is read and written without the GIL, which `PyFileObject` only
+/// takes back for the duration of its calls. `progress` is called with the
+/// total number of bytes copied after each chunk.
+pub fn copy_chunks<R, W>(
+    py: Python,
+    reader: &mut R,
+    writer: &mut W,
+    chunk_size: usize,
+    progress: &Option<PyObject>,
+) -> PyResult<u64>
+where
+    R: Read + Send,
+    W: Write + Send,
+{
+    let mut buf = vec![0; chunk_size.max(1)];
+    let mut total = 0;
+    loop {
+        let read = match py.allow_threads(|| reader.read(&mut buf)) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(to_pyerr(err)),
+        };
+        let chunk = &buf[..read];
+        py.allow_threads(|| writer.write_all(chunk)).map_err(to_pyerr)?;
+        total += read as u64;
+
+        if let Some(ref callback) = *progress {
+            callback.call(py, (total,), None)?;
+        }
+    }
+}
+
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub trait WriteSeek: Write + Seek {}
+impl<T: Write + Seek> WriteSeek for T {}
+
+
+/// Open a host path, or wrap a Python file object, for reading.
+pub fn reader(source: &PyObjectRef) -> PyResult<Box<ReadSeek>> {
+    match source.extract::<String>() {
+        Ok(path) => Ok(Box::new(::std::fs::File::open(path)?)),
+        Err(_) => Ok(Box::new(PyFileObject::new(source.into()))),
+    }
+}
+
+
+/// Create a host path, or wrap a Python file object, for writing.
+pub fn writer(target: &PyObjectRef) -> PyResult<Box<WriteSeek>> {
+    match target.extract::<String>() {
+        Ok(path) => Ok(Box::new(::std::fs::File::create(path)?)),
+        Err(_) => Ok(Box::new(PyFileObject::new(target.into()))),
+    }
+}
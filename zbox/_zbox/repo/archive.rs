@@ -0,0 +1,221 @@
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use tar::{Archive, Builder, EntryType, Header};
+use zbox::Repo;
+use zbox::Result;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+
+use ::repo::report::Report;
+use ::repo::walk;
+
+
+/// Turn an archive member name into a relative path, refusing to leave the
+/// extraction directory.
+pub fn sanitize(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => (),
+            Component::ParentDir => return None,
+        }
+    }
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+
+/// Make the paths of an exported entry relative to the exported directory.
+pub fn relative_to(path: &Path, top: &Path) -> PathBuf {
+    path.strip_prefix(top).unwrap_or(path).to_path_buf()
+}
+
+
+fn modified_secs(meta: &::zbox::Metadata) -> u64 {
+    meta.modified().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+
+/// Write the tree below `top` as a tar stream, optionally gzip-compressed.
+///
+/// Returns the number of files written to the archive.
+pub fn export_tar<W: Write>(repo: &mut Repo, top: &Path, target: W, gzip: bool) -> Result<usize> {
+    if gzip {
+        let encoder = GzEncoder::new(target, Compression::default());
+        let (encoder, count) = write_tar(repo, top, encoder)?;
+        encoder.finish()?;
+        Ok(count)
+    } else {
+        write_tar(repo, top, target).map(|(_, count)| count)
+    }
+}
+
+fn write_tar<W: Write>(repo: &mut Repo, top: &Path, target: W) -> Result<(W, usize)> {
+    let mut builder = Builder::new(target);
+    let mut count = 0;
+
+    // `append_data` sets the path and checksum, writing a GNU long name
+    // extension for paths that do not fit in the header
+    for entry in walk::walk(repo, top)? {
+        let meta = entry.metadata();
+        let path = relative_to(entry.path(), top);
+        let mut header = Header::new_gnu();
+        header.set_mtime(modified_secs(&meta));
+
+        if meta.is_dir() {
+            header.set_entry_type(EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder.append_data(&mut header, path, io::empty())?;
+        } else {
+            header.set_entry_type(EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_size(meta.len() as u64);
+            builder.append_data(&mut header, path, repo.open_file(entry.path())?)?;
+            count += 1;
+        }
+    }
+
+    Ok((builder.into_inner()?, count))
+}
+
+
+/// Extract a tar stream, compressed with gzip or not, into the directory `dst`.
+///
+/// Repositories record their own modification times, so the ones stored in
+/// the archive can not be kept. Links and special files are skipped.
+pub fn import_tar<R: Read>(repo: &mut Repo, source: R, dst: &Path) -> Result<Report> {
+    let mut reader = BufReader::new(source);
+    let gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if gzip {
+        read_tar(repo, GzDecoder::new(reader), dst)
+    } else {
+        read_tar(repo, reader, dst)
+    }
+}
+
+fn read_tar<R: Read>(repo: &mut Repo, source: R, dst: &Path) -> Result<Report> {
+    let mut report = Report::default();
+    let mut archive = Archive::new(source);
+
+    if !repo.is_dir(dst) {
+        repo.create_dir_all(dst)?;
+    }
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let target = match sanitize(&name) {
+            Some(relpath) => dst.join(relpath),
+            None => {
+                report.skipped.push(name.to_string_lossy().into_owned());
+                continue;
+            }
+        };
+
+        match entry.header().entry_type() {
+            EntryType::Directory => if !repo.is_dir(&target) {
+                repo.create_dir_all(&target)?;
+            },
+            EntryType::Regular | EntryType::Continuous => {
+                if let Some(parent) = target.parent() {
+                    if !repo.is_dir(parent) {
+                        repo.create_dir_all(parent)?;
+                    }
+                }
+                let mut file = repo.create_file(&target)?;
+                io::copy(&mut entry, &mut file)?;
+                file.finish()?;
+                report.copied.push(target.to_string_lossy().into_owned());
+            }
+            _ => report.skipped.push(name.to_string_lossy().into_owned()),
+        }
+    }
+
+    Ok(report)
+}
+
+
+fn from_zip_error(err: ZipError) -> ::zbox::Error {
+    match err {
+        ZipError::Io(err) => ::zbox::Error::from(err),
+        other => ::zbox::Error::from(io::Error::new(io::ErrorKind::InvalidData, other.to_string())),
+    }
+}
+
+
+/// Write the tree below `top` as a zip archive.
+///
+/// Returns the number of files written to the archive.
+pub fn export_zip<W: Write + Seek>(repo: &mut Repo, top: &Path, target: W, method: CompressionMethod) -> Result<usize> {
+    let mut writer = ZipWriter::new(target);
+    let options = FileOptions::default().compression_method(method);
+    let mut count = 0;
+
+    for entry in walk::walk(repo, top)? {
+        // Zip member names always use forward slashes
+        let name = relative_to(entry.path(), top).to_string_lossy().into_owned();
+        if entry.metadata().is_dir() {
+            writer.add_directory(format!("{}/", name), options).map_err(from_zip_error)?;
+        } else {
+            writer.start_file(name, options).map_err(from_zip_error)?;
+            io::copy(&mut repo.open_file(entry.path())?, &mut writer)?;
+            count += 1;
+        }
+    }
+
+    writer.finish().map_err(from_zip_error)?;
+    Ok(count)
+}
+
+
+/// Extract a zip archive into the directory `dst`.
+pub fn import_zip<R: Read + Seek>(repo: &mut Repo, source: R, dst: &Path) -> Result<Report> {
+    let mut report = Report::default();
+    let mut archive = ZipArchive::new(source).map_err(from_zip_error)?;
+
+    if !repo.is_dir(dst) {
+        repo.create_dir_all(dst)?;
+    }
+
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index).map_err(from_zip_error)?;
+        let name = member.name().to_owned();
+        let target = match sanitize(Path::new(&name)) {
+            Some(relpath) => dst.join(relpath),
+            None => {
+                report.skipped.push(name);
+                continue;
+            }
+        };
+
+        if name.ends_with('/') {
+            if !repo.is_dir(&target) {
+                repo.create_dir_all(&target)?;
+            }
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            if !repo.is_dir(parent) {
+                repo.create_dir_all(parent)?;
+            }
+        }
+        let mut file = repo.create_file(&target)?;
+        io::copy(&mut member, &mut file)?;
+        file.finish()?;
+        report.copied.push(target.to_string_lossy().into_owned());
+    }
+
+    Ok(report)
+}
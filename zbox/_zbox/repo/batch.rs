@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use pyo3::py::*;
+use pyo3::buffer::PyBuffer;
+use pyo3::class::context::*;
+use pyo3::exc;
+
+use ::file::File;
+use ::file::Mode;
+use ::repo::errors::Error;
+use ::repo::repo::Repo;
+use ::repo::snapshot::restore_version;
+
+
+/// The directory where entries removed during a batch are held until it ends.
+pub const HOLDING_DIR: &str = "/.zbox/batch";
+
+
+macro_rules! check_pending {
+    ($batch: expr) => {
+        if $batch.finished {
+            return Err(exc::ValueError::new("batch already committed or rolled back"));
+        }
+    };
+}
+
+
+/// A change made during a batch, with what is needed to undo it.
+#[derive(Debug)]
+enum Action {
+    /// A file was written to; holds its version before the batch, if it existed.
+    Modified(PathBuf, Option<usize>),
+    /// An entry was removed; holds the path it was moved to in the holding area.
+    Removed(PathBuf, PathBuf),
+    /// An entry was renamed from the first path to the second one.
+    Renamed(PathBuf, PathBuf),
+}
+
+
+#[class]
+pub struct Batch {
+    repo: Py<Repo>,
+    holding: PathBuf,
+    journal: Vec<Action>,
+    touched: HashSet<PathBuf>,
+    finished: bool,
+    token: PyToken,
+}
+
+
+impl Batch {
+
+    pub fn new(token: PyToken, repo: Py<Repo>) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            token,
+            repo,
+            holding: Path::new(HOLDING_DIR).join(nanos.to_string()),
+            journal: Vec::new(),
+            touched: HashSet::new(),
+            finished: false,
+        }
+    }
+
+    /// Record the state of a file the first time it is about to be modified.
+    fn track(&mut self, path: &Path) -> PyResult<()> {
+        if !self.touched.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+        let repo = self.repo.as_mut(self.token.py()).inner();
+        let version = match repo.metadata(path) {
+            Ok(ref meta) if meta.is_file() => Some(meta.curr_version()),
+            Ok(_) => return Error::from(::zbox::Error::IsDir).into(),
+            Err(::zbox::Error::NotFound) => None,
+            Err(err) => return Error::from(err).into(),
+        };
+        self.journal.push(Action::Modified(path.to_path_buf(), version));
+        Ok(())
+    }
+
+    /// Move an entry to the holding area, so that it can be brought back.
+    fn hold(&mut self, path: &Path) -> PyResult<()> {
+        let held = self.holding.join(self.journal.len().to_string());
+        let repo = self.repo.as_mut(self.token.py()).inner();
+        if !repo.is_dir(&self.holding) {
+            repo.create_dir_all(&self.holding).map_err(Error::from)?;
+        }
+        repo.rename(path, &held).map_err(Error::from)?;
+        self.journal.push(Action::Removed(path.to_path_buf(), held));
+        Ok(())
+    }
+
+    fn undo(repo: &mut ::zbox::Repo, action: &Action) -> ::zbox::Result<()> {
+        match *action {
+            Action::Modified(ref path, Some(version)) => {
+                if repo.metadata(path)?.curr_version() != version {
+                    restore_version(repo, path, version)?;
+                }
+                Ok(())
+            }
+            Action::Modified(ref path, None) if repo.is_file(path) => repo.remove_file(path),
+            Action::Modified(_, None) => Ok(()),
+            Action::Removed(ref path, ref held) => repo.rename(held, path),
+            Action::Renamed(ref from, ref to) => repo.rename(to, from),
+        }
+    }
+
+    fn cleanup(&mut self) -> PyResult<()> {
+        self.finished = true;
+        let repo = self.repo.as_mut(self.token.py()).inner();
+        if repo.is_dir(&self.holding) {
+            repo.remove_dir_all(&self.holding).map_err(Error::from)?;
+        }
+        if repo.read_dir(HOLDING_DIR).map(|e| e.is_empty()).unwrap_or(false) {
+            repo.remove_dir(HOLDING_DIR).map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Surface the paths a rollback failed to restore, since `__exit__`
+    /// lets the original exception propagate.
+    fn report_failures(&self, exception: Option<&PyObjectRef>, failed: Vec<String>) -> PyResult<()> {
+        let message = format!(
+            "batch rollback could not restore {}, held entries are kept in {}",
+            failed.join(", "),
+            self.holding.display(),
+        );
+        if let Some(exception) = exception {
+            exception.setattr("rollback_failures", failed)?;
+        }
+        self.token.py().import("warnings")?.call("warn", (message,), None)?;
+        Ok(())
+    }
+}
+
+
+#[methods]
+impl Batch {
+
+    #[args(mode = "\"r\"")]
+    fn open(&mut self, path: &str, mode: &str) -> PyResult<Py<File>> {
+        check_pending!(self);
+        if Mode::from(mode).writing {
+            self.track(Path::new(path))?;
+        }
+        self.repo.as_mut(self.token.py()).open_with_mode(path, mode)
+    }
+
+    fn write(&mut self, path: &str, data: &PyObjectRef) -> PyResult<()> {
+        check_pending!(self);
+        let py = self.token.py();
+        let buffer = PyBuffer::get(py, data)?;
+        let bytes = buffer.to_vec::<u8>(py)?;
+
+        self.track(Path::new(path))?;
+        let repo = self.repo.as_mut(py).inner();
+        let mut file = repo.create_file(path).map_err(Error::from)?;
+        file.write_all(&bytes)?;
+        file.finish().map_err(|err| Error::from(err).into())
+    }
+
+    fn remove(&mut self, path: &str) -> PyResult<()> {
+        check_pending!(self);
+        if !self.repo.as_mut(self.token.py()).inner().path_exists(path) {
+            return Error::from(::zbox::Error::NotFound).into();
+        }
+        self.hold(Path::new(path))
+    }
+
+    fn rename(&mut self, src: &str, dst: &str) -> PyResult<()> {
+        check_pending!(self);
+        let (src_exists, dst_exists) = {
+            let repo = self.repo.as_mut(self.token.py()).inner();
+            (repo.path_exists(src), repo.path_exists(dst))
+        };
+        if !src_exists {
+            return Error::from(::zbox::Error::NotFound).into();
+        }
+        // Keep an overwritten destination around until the batch ends
+        if dst_exists {
+            self.hold(Path::new(dst))?;
+        }
+
+        let repo = self.repo.as_mut(self.token.py()).inner();
+        repo.rename(src, dst).map_err(Error::from)?;
+        self.journal.push(Action::Renamed(PathBuf::from(src), PathBuf::from(dst)));
+        Ok(())
+    }
+
+    fn commit(&mut self) -> PyResult<()> {
+        check_pending!(self);
+        self.cleanup()
+    }
+
+    /// Undo every change of the batch, returning the paths that failed to be restored.
+    ///
+    /// Removed entries that could not be put back are left in the holding area.
+    fn rollback(&mut self) -> PyResult<Vec<String>> {
+        check_pending!(self);
+        let mut failed = Vec::new();
+        {
+            let repo = self.repo.as_mut(self.token.py()).inner();
+            while let Some(action) = self.journal.pop() {
+                if Self::undo(repo, &action).is_err() {
+                    let path = match action {
+                        Action::Modified(path, _) => path,
+                        Action::Removed(path, _) => path,
+                        Action::Renamed(from, _) => from,
+                    };
+                    failed.push(path.to_string_lossy().into_owned());
+                }
+            }
+        }
+        if failed.is_empty() {
+            self.cleanup()?;
+        } else {
+            self.finished = true;
+        }
+        Ok(failed)
+    }
+}
+
+
+#[proto]
+impl<'p> PyContextProtocol<'p> for Batch {
+
+    fn __enter__(&mut self) -> PyResult<PyObject> {
+        Ok(self.to_object(self.token.py()))
+    }
+
+    fn __exit__(
+        &mut self,
+        ty: Option<&'p PyType>,
+        value: Option<&'p PyObjectRef>,
+        traceback: Option<&'p PyObjectRef>
+    ) -> PyResult<bool> {
+        if !self.finished {
+            match ty {
+                Some(_) => {
+                    let failed = self.rollback()?;
+                    if !failed.is_empty() {
+                        self.report_failures(value, failed)?;
+                    }
+                }
+                None => { self.commit()?; }
+            }
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::report::Changes;
+use ::repo::snapshot::Snapshot;
+use ::repo::walk;
+
+
+/// List the files below `top` created or modified after `when`.
+///
+/// Removed files leave no trace in the repository, so they can only be
+/// detected when comparing with a snapshot.
+pub fn since_time(repo: &Repo, top: &Path, when: SystemTime) -> Result<Changes> {
+    let mut changes = Changes::default();
+    for entry in walk::walk_files(repo, top)? {
+        let meta = entry.metadata();
+        let path = entry.path().to_string_lossy().into_owned();
+        if meta.created() > when {
+            changes.added.push(path);
+        } else if meta.modified() > when {
+            changes.modified.push(path);
+        }
+    }
+    Ok(changes)
+}
+
+
+/// List the files below `top` added, modified or removed since a snapshot was taken.
+pub fn since_snapshot(repo: &Repo, top: &Path, snapshot: &Snapshot) -> Result<Changes> {
+    let mut changes = Changes::default();
+    let mut seen = HashSet::new();
+
+    for entry in walk::walk_files(repo, top)? {
+        let path = entry.path().to_string_lossy().into_owned();
+        match snapshot.files.get(&path) {
+            None => changes.added.push(path.clone()),
+            Some(&v) if v != entry.metadata().curr_version() => changes.modified.push(path.clone()),
+            Some(_) => (),
+        }
+        seen.insert(path);
+    }
+
+    for path in snapshot.files.keys() {
+        if Path::new(path).starts_with(top) && !seen.contains(path) {
+            changes.removed.push(path.clone());
+        }
+    }
+
+    Ok(changes)
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Read};
+
+use blake2::Blake2b;
+use digest::Digest;
+use md5::Md5;
+use sha2::Sha256;
+use zbox::File;
+use zbox::Result;
+
+
+/// A hash algorithm supported to compute file checksums.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Blake2b,
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "blake2b" => Some(Algorithm::Blake2b),
+            "md5" => Some(Algorithm::Md5),
+            "sha256" => Some(Algorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Hash the whole content of a reader, returning the hexadecimal digest.
+    pub fn hash_reader<R: Read>(&self, reader: &mut R) -> io::Result<String> {
+        let digest = match *self {
+            Algorithm::Blake2b => digest_reader::<Blake2b, R>(reader)?,
+            Algorithm::Md5 => digest_reader::<Md5, R>(reader)?,
+            Algorithm::Sha256 => digest_reader::<Sha256, R>(reader)?,
+        };
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+
+fn digest_reader<D: Digest + Default, R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut hasher = D::default();
+    let mut buffer = [0; 65536];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(hasher.result().to_vec()),
+            n => hasher.input(&buffer[..n]),
+        }
+    }
+}
+
+
+/// Compute the checksum of a file, or of one of its versions.
+///
+/// The file is taken by value so that it can be hashed without the GIL.
+pub fn checksum(mut file: File, algorithm: Algorithm, version: Option<usize>) -> Result<String> {
+    let digest = match version {
+        Some(num) => algorithm.hash_reader(&mut file.version_reader(num)?)?,
+        None => algorithm.hash_reader(&mut file)?,
+    };
+    Ok(digest)
+}
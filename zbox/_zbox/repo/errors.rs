@@ -39,14 +39,14 @@ impl ::std::convert::From<::zbox::Error> for Error {
 }
 
 
-impl ::std::convert::Into<PyErr> for Error {
+impl ::std::convert::From<Error> for PyErr {
 
-    fn into(self) -> PyErr {
+    fn from(err: Error) -> PyErr {
 
         use std::error::Error;
         use zbox::Error::*;
 
-        match self.0 {
+        match err.0 {
 
             // RefOverflow,
             // RefUnderflow,
@@ -88,7 +88,7 @@ impl ::std::convert::Into<PyErr> for Error {
             // Encode(err) => exc::UnicodeDecodeError::new(err.description().to_string()),
             // Decode(DecodeError),
             // Var(VarError),
-            // Io(IoError),
+            Io(err) => ::pyio::to_pyerr(err),
 
             err => exc::RuntimeError::new(err.description().to_string()),
         }
@@ -0,0 +1,93 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::archive::relative_to;
+use ::repo::walk;
+
+
+/// A retained version of a file, as found in its history.
+struct Revision {
+    created: SystemTime,
+    path: String,
+    num: usize,
+    len: usize,
+}
+
+
+/// Write a `git fast-import` stream replaying the retained versions of every
+/// file below `top`, one commit per version, oldest first.
+///
+/// Returns the number of commits written.
+pub fn export_fast_import<W: Write>(repo: &mut Repo, top: &Path, out: &mut W) -> Result<usize> {
+    let mut revisions = Vec::new();
+    for entry in walk::walk_files(repo, top)? {
+        let path = entry.path().to_string_lossy().into_owned();
+        for version in repo.history(entry.path())? {
+            revisions.push(Revision {
+                created: version.created(),
+                path: path.clone(),
+                num: version.num(),
+                len: version.len(),
+            });
+        }
+    }
+    revisions.sort_by(|a, b| (a.created, &a.path, a.num).cmp(&(b.created, &b.path, b.num)));
+
+    for (index, revision) in revisions.iter().enumerate() {
+        let blob_mark = 2 * index + 1;
+        let commit_mark = 2 * index + 2;
+        let path = relative_to(Path::new(&revision.path), top);
+        let secs = revision.created.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let message = format!("{} (version {})\n", revision.path, revision.num);
+
+        write!(out, "blob\nmark :{}\ndata {}\n", blob_mark, revision.len)?;
+        let file = repo.open_file(&revision.path)?;
+        let copied = io::copy(&mut file.version_reader(revision.num)?.take(revision.len as u64), out)?;
+        if copied != revision.len as u64 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated version").into());
+        }
+
+        write!(out, "\ncommit refs/heads/master\nmark :{}\n", commit_mark)?;
+        write!(out, "committer zbox <zbox@localhost> {} +0000\n", secs)?;
+        write!(out, "data {}\n{}", message.len(), message)?;
+        if index > 0 {
+            write!(out, "from :{}\n", commit_mark - 2)?;
+        }
+        write!(out, "M 100644 :{} {}\n\n", blob_mark, quote_path(&path.to_string_lossy()))?;
+    }
+
+    out.flush()?;
+    Ok(revisions.len())
+}
+
+
+/// Quote `path` the way `git fast-import` expects when it starts with a double
+/// quote or contains control characters, and return it unchanged otherwise.
+fn quote_path(path: &str) -> String {
+    if !path.starts_with('"') && !path.chars().any(|c| c.is_control()) {
+        return path.to_owned();
+    }
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('"');
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    quoted.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,108 @@
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+use pyo3::py::*;
+use regex::Regex;
+
+use ::repo::errors::Error;
+use ::repo::repo::Repo;
+
+
+/// Files with a NUL byte in their first block are considered binary.
+const BINARY_CHECK_SIZE: usize = 8192;
+
+
+/// An iterator over the lines of repository files matching a pattern.
+#[class]
+pub struct Matches {
+    repo: Py<Repo>,
+    regex: Regex,
+    files: Vec<PathBuf>,
+    current: Option<(String, BufReader<::zbox::File>, usize)>,
+    remaining: Option<usize>,
+    token: PyToken,
+}
+
+
+impl Matches {
+
+    pub fn new(
+        token: PyToken,
+        repo: Py<Repo>,
+        regex: Regex,
+        mut files: Vec<PathBuf>,
+        max_matches: Option<usize>,
+    ) -> Self {
+        // Files are popped from the end, so reverse to scan them in order
+        files.reverse();
+        Self { token, repo, regex, files, current: None, remaining: max_matches }
+    }
+
+    /// Open the next text file to scan, returning `false` when there are none left.
+    fn open_next(&mut self) -> PyResult<bool> {
+        let repo = self.repo.as_mut(self.token.py()).inner();
+        while let Some(path) = self.files.pop() {
+            let mut file = repo.open_file(&path).map_err(Error::from)?;
+
+            let mut head = Vec::with_capacity(BINARY_CHECK_SIZE);
+            (&mut file).take(BINARY_CHECK_SIZE as u64).read_to_end(&mut head)?;
+            if head.contains(&0) {
+                continue;
+            }
+
+            file.seek(SeekFrom::Start(0))?;
+            let name = path.to_string_lossy().into_owned();
+            self.current = Some((name, BufReader::new(file), 0));
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+
+#[proto]
+impl PyIterProtocol for Matches {
+
+    fn __iter__(&mut self) -> PyResult<PyObject> {
+        Ok(self.into())
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<(String, usize, String)>> {
+        let mut line = Vec::new();
+        loop {
+            if self.remaining == Some(0) {
+                return Ok(None);
+            }
+            if self.current.is_none() && !self.open_next()? {
+                return Ok(None);
+            }
+
+            let found = {
+                let (ref path, ref mut reader, ref mut line_no) = *self.current.as_mut().unwrap();
+                line.clear();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    None
+                } else {
+                    *line_no += 1;
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim_right_matches(|c| c == '\n' || c == '\r');
+                    if self.regex.is_match(text) {
+                        Some(Some((path.clone(), *line_no, text.to_owned())))
+                    } else {
+                        Some(None)
+                    }
+                }
+            };
+
+            match found {
+                None => self.current = None,
+                Some(None) => (),
+                Some(Some(hit)) => {
+                    self.remaining = self.remaining.map(|n| n - 1);
+                    return Ok(Some(hit));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use filetime::FileTime;
+use glob::Pattern;
+use zbox::Error;
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::checksum::Algorithm;
+use ::repo::report::{Comparison, Report, SyncReport};
+use ::repo::transfer::SYNC_DIR;
+use ::repo::trash;
+use ::repo::walk::is_internal;
+use ::utils::same_content;
+
+
+/// Options controlling how a synchronisation decides and applies changes.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    pub delete: bool,
+    pub checksum: bool,
+    pub dry_run: bool,
+    /// Move replaced and deleted entries to the trash instead of removing them.
+    pub trash: bool,
+}
+
+
+/// Options controlling which host entries an import copies.
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions<'a> {
+    pub follow_symlinks: bool,
+    pub exclude: &'a [Pattern],
+}
+
+
+fn is_excluded(exclude: &[Pattern], name: &str, relpath: &Path) -> bool {
+    exclude.iter().any(|p| p.matches(name) || p.matches_path(relpath))
+}
+
+
+/// Read the metadata of a host entry, following symbolic links if asked to.
+///
+/// Returns `None` for links that can not be followed, such as dangling links.
+fn host_metadata(path: &Path, follow_symlinks: bool) -> io::Result<Option<fs::Metadata>> {
+    let meta = fs::symlink_metadata(path)?;
+    if follow_symlinks && meta.file_type().is_symlink() {
+        Ok(fs::metadata(path).ok())
+    } else {
+        Ok(Some(meta))
+    }
+}
+
+
+/// Identify a host directory by device and inode, to detect symbolic link loops.
+#[cfg(unix)]
+fn dir_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+
+/// Recursively copy the host directory `src` into the repository directory `dst`.
+pub fn import_tree(repo: &mut Repo, src: &Path, dst: &Path, opts: ImportOptions) -> Result<Report> {
+    let mut report = Report::default();
+    if !repo.is_dir(dst) {
+        repo.create_dir_all(dst)?;
+    }
+    let mut ancestors: HashSet<_> = dir_id(&fs::metadata(src)?).into_iter().collect();
+    import_dir(repo, src, dst, Path::new(""), opts, &mut ancestors, &mut report)?;
+    Ok(report)
+}
+
+fn import_dir(
+    repo: &mut Repo,
+    src: &Path,
+    dst: &Path,
+    relpath: &Path,
+    opts: ImportOptions,
+    ancestors: &mut HashSet<(u64, u64)>,
+    report: &mut Report,
+) -> Result<()> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let host_path = entry.path();
+        let repo_path = dst.join(&name);
+        let repo_name = repo_path.to_string_lossy().into_owned();
+        let entry_relpath = relpath.join(&name);
+
+        if is_excluded(opts.exclude, &name, &entry_relpath) || is_internal(&repo_path) {
+            report.skipped.push(repo_name);
+            continue;
+        }
+
+        // Links are skipped when not followed, or when they can not be
+        let meta = match host_metadata(&host_path, opts.follow_symlinks)? {
+            Some(meta) => meta,
+            None => {
+                report.skipped.push(repo_name);
+                continue;
+            }
+        };
+
+        if meta.file_type().is_symlink() {
+            report.skipped.push(repo_name);
+        } else if meta.is_dir() {
+            // A directory that is its own ancestor was reached through a link loop
+            let id = dir_id(&meta);
+            if id.map_or(false, |id| !ancestors.insert(id)) {
+                report.skipped.push(repo_name);
+                continue;
+            }
+            if !repo.is_dir(&repo_path) {
+                repo.create_dir(&repo_path)?;
+            }
+            import_dir(repo, &host_path, &repo_path, &entry_relpath, opts, ancestors, report)?;
+            if let Some(id) = id {
+                ancestors.remove(&id);
+            }
+        } else if meta.is_file() {
+            write_host_file(repo, &host_path, &repo_path)?;
+            report.copied.push(repo_name);
+        } else {
+            // Sockets, FIFOs and devices can not be stored in a repository
+            report.skipped.push(repo_name);
+        }
+    }
+    Ok(())
+}
+
+
+/// Copy a single host file to a new version of a repository file.
+fn write_host_file(repo: &mut Repo, src: &Path, dst: &Path) -> Result<()> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = repo.create_file(dst)?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.finish()
+}
+
+
+/// Recursively copy the repository directory `src` into the host directory `dst`.
+pub fn export_tree(repo: &mut Repo, src: &Path, dst: &Path, overwrite: bool) -> Result<Report> {
+    let mut report = Report::default();
+    fs::create_dir_all(dst)?;
+    export_dir(repo, src, dst, overwrite, &mut report)?;
+    Ok(report)
+}
+
+fn export_dir(
+    repo: &mut Repo,
+    src: &Path,
+    dst: &Path,
+    overwrite: bool,
+    report: &mut Report,
+) -> Result<()> {
+    for entry in repo.read_dir(src)? {
+        if is_internal(entry.path()) {
+            continue;
+        }
+        let host_path = dst.join(entry.file_name());
+        let meta = entry.metadata();
+
+        if meta.is_dir() {
+            if !host_path.is_dir() {
+                fs::create_dir(&host_path)?;
+            }
+            export_dir(repo, entry.path(), &host_path, overwrite, report)?;
+        } else if host_path.exists() && !overwrite {
+            report.skipped.push(entry.path().to_string_lossy().into_owned());
+            continue;
+        } else {
+            let mut reader = repo.open_file(entry.path())?;
+            let mut writer = fs::File::create(&host_path)?;
+            io::copy(&mut reader, &mut writer)?;
+            report.copied.push(entry.path().to_string_lossy().into_owned());
+        }
+
+        // Set the modification time last, since writing children updates it
+        let mtime = FileTime::from_system_time(meta.modified());
+        ::filetime::set_file_times(&host_path, mtime, mtime)?;
+    }
+    Ok(())
+}
+
+
+/// The state of a host file when a previous `sync_from_host` last saw it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct HostRecord {
+    size: u64,
+    modified: (u64, u32),
+    version: usize,
+}
+
+impl HostRecord {
+    fn new(meta: &fs::Metadata, version: usize) -> io::Result<Self> {
+        let modified = meta.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| (d.as_secs(), d.subsec_nanos()))
+            .unwrap_or((0, 0));
+        Ok(HostRecord { size: meta.len(), modified, version })
+    }
+}
+
+
+/// The host files copied by a previous `sync_from_host` between the same two trees.
+///
+/// Each file is recorded with the size and modification time it had on the
+/// host, and the repository version it was copied to, so that a later sync
+/// notices any change on either side, even one going back in time.
+#[derive(Debug, Default)]
+struct HostState {
+    path: PathBuf,
+    recorded: BTreeMap<String, HostRecord>,
+    synced: BTreeMap<String, HostRecord>,
+}
+
+impl HostState {
+    fn load(repo: &mut Repo, src: &Path, dst: &Path) -> Result<Self> {
+        let key = format!("host\n{}\n{}", fs::canonicalize(src)?.display(), dst.display());
+        let name = Algorithm::Sha256.hash_reader(&mut key.as_bytes())?;
+        let mut state = HostState { path: Path::new(SYNC_DIR).join(name), ..Default::default() };
+        if !repo.is_file(&state.path) {
+            return Ok(state);
+        }
+
+        let file = repo.open_file(&state.path)?;
+        state.recorded = ::serde_json::from_reader(file).map_err(|_| Error::Corrupted)?;
+        Ok(state)
+    }
+
+    /// Replace the recorded files with the ones seen by this sync.
+    fn save(&self, repo: &mut Repo) -> Result<()> {
+        if !repo.is_dir(SYNC_DIR) {
+            repo.create_dir_all(SYNC_DIR)?;
+        }
+        let mut file = repo.create_file(&self.path)?;
+        file.write_all(&::serde_json::to_vec(&self.synced).map_err(io::Error::from)?)?;
+        file.finish()
+    }
+}
+
+
+/// Update the repository directory `dst` so that it mirrors the host directory `src`.
+///
+/// A file is copied when it is missing from the repository, or when its size
+/// or modification time on the host, or its repository version, changed since
+/// the last sync. Files this sync has no record of are compared by content.
+pub fn sync_from_host(repo: &mut Repo, src: &Path, dst: &Path, opts: SyncOptions) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    if !repo.is_dir(dst) && !opts.dry_run {
+        repo.create_dir_all(dst)?;
+    }
+    let mut state = HostState::load(repo, src, dst)?;
+    let mut ancestors: HashSet<_> = dir_id(&fs::metadata(src)?).into_iter().collect();
+    sync_dir(repo, src, dst, opts, &mut ancestors, &mut state, &mut report)?;
+    if !opts.dry_run {
+        state.save(repo)?;
+    }
+    Ok(report)
+}
+
+fn sync_dir(
+    repo: &mut Repo,
+    src: &Path,
+    dst: &Path,
+    opts: SyncOptions,
+    ancestors: &mut HashSet<(u64, u64)>,
+    state: &mut HostState,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let repo_path = dst.join(&name);
+        let repo_name = repo_path.to_string_lossy().into_owned();
+
+        // Dangling links and link loops are skipped, leaving the repository as is
+        let meta = host_metadata(&entry.path(), true)?;
+        let id = meta.as_ref().and_then(|m| if m.is_dir() { dir_id(m) } else { None });
+        let looped = id.map_or(false, |id| ancestors.contains(&id)) || is_internal(&repo_path);
+        let meta = match meta.filter(|_| !looped) {
+            Some(meta) => meta,
+            None => {
+                seen.insert(name);
+                report.skipped.push(repo_name);
+                continue;
+            }
+        };
+
+        if meta.is_dir() {
+            seen.insert(name);
+            if repo.is_file(&repo_path) {
+                report.deleted.push(repo_name.clone());
+                if !opts.dry_run {
+                    trash::remove(repo, &repo_path, opts.trash)?;
+                }
+            }
+            if !repo.is_dir(&repo_path) && !opts.dry_run {
+                repo.create_dir(&repo_path)?;
+            }
+            if let Some(id) = id {
+                ancestors.insert(id);
+            }
+            sync_dir(repo, &entry.path(), &repo_path, opts, ancestors, state, report)?;
+            if let Some(id) = id {
+                ancestors.remove(&id);
+            }
+        } else if meta.is_file() {
+            seen.insert(name);
+            if repo.is_dir(&repo_path) {
+                report.deleted.push(repo_name.clone());
+                if !opts.dry_run {
+                    trash::remove(repo, &repo_path, opts.trash)?;
+                }
+            }
+            let changed = if !repo.is_file(&repo_path) {
+                report.added.push(repo_name.clone());
+                true
+            } else {
+                let recorded = state.recorded.get(&repo_name).filter(|_| !opts.checksum);
+                let changed = host_file_changed(repo, &entry.path(), &meta, &repo_path, recorded)?;
+                if changed {
+                    report.updated.push(repo_name.clone());
+                }
+                changed
+            };
+            if !opts.dry_run {
+                if changed {
+                    write_host_file(repo, &entry.path(), &repo_path)?;
+                }
+                let version = repo.metadata(&repo_path)?.curr_version();
+                state.synced.insert(repo_name, HostRecord::new(&meta, version)?);
+            }
+        }
+    }
+
+    // Remove repository entries that are not on the host anymore
+    if opts.delete && repo.is_dir(dst) {
+        for entry in repo.read_dir(dst)? {
+            if seen.contains(entry.file_name()) || is_internal(entry.path()) {
+                continue;
+            }
+            report.deleted.push(entry.path().to_string_lossy().into_owned());
+            if !opts.dry_run {
+                trash::remove(repo, entry.path(), opts.trash)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether a host file differs from its repository counterpart.
+///
+/// Without a `recorded` state from the previous sync, both contents are compared.
+fn host_file_changed(
+    repo: &mut Repo,
+    host_path: &Path,
+    host_meta: &fs::Metadata,
+    repo_path: &Path,
+    recorded: Option<&HostRecord>,
+) -> Result<bool> {
+    let repo_meta = repo.metadata(repo_path)?;
+    if host_meta.len() != repo_meta.len() as u64 {
+        Ok(true)
+    } else if let Some(recorded) = recorded {
+        Ok(*recorded != HostRecord::new(host_meta, repo_meta.curr_version())?)
+    } else {
+        let mut host_file = fs::File::open(host_path)?;
+        let mut repo_file = repo.open_file(repo_path)?;
+        Ok(!same_content(&mut host_file, &mut repo_file)?)
+    }
+}
+
+
+/// Compare the repository directory `repo_dir` with the host directory `host_dir`.
+///
+/// Files are compared by size and modification time (to the second), or by
+/// streaming both contents when `by_content` is set.
+pub fn compare(repo: &mut Repo, repo_dir: &Path, host_dir: &Path, by_content: bool) -> Result<Comparison> {
+    let mut comparison = Comparison::default();
+    compare_dir(repo, repo_dir, host_dir, Path::new(""), by_content, &mut comparison)?;
+    comparison.repo_only.sort();
+    comparison.host_only.sort();
+    comparison.different.sort();
+    comparison.skipped.sort();
+    Ok(comparison)
+}
+
+fn compare_dir(
+    repo: &mut Repo,
+    repo_dir: &Path,
+    host_dir: &Path,
+    relpath: &Path,
+    by_content: bool,
+    comparison: &mut Comparison,
+) -> Result<()> {
+    let mut host_entries = BTreeMap::new();
+    let mut skipped = BTreeSet::new();
+    for entry in fs::read_dir(host_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        match host_metadata(&entry.path(), true)? {
+            Some(meta) => {
+                host_entries.insert(name, meta);
+            }
+            None => {
+                skipped.insert(name);
+            }
+        }
+    }
+
+    for entry in repo.read_dir(repo_dir)? {
+        if is_internal(entry.path()) {
+            continue;
+        }
+        let name = entry.file_name().to_owned();
+        let entry_relpath = relpath.join(&name);
+        let relname = entry_relpath.to_string_lossy().into_owned();
+        let repo_meta = entry.metadata();
+
+        let host_meta = match host_entries.remove(&name) {
+            Some(meta) => meta,
+            None if skipped.contains(&name) => continue,
+            None => {
+                comparison.repo_only.push(relname);
+                continue;
+            }
+        };
+
+        let host_path = host_dir.join(&name);
+        if repo_meta.is_dir() != host_meta.is_dir() {
+            comparison.different.push(relname);
+        } else if repo_meta.is_dir() {
+            compare_dir(repo, entry.path(), &host_path, &entry_relpath, by_content, comparison)?;
+        } else if repo_meta.len() as u64 != host_meta.len() {
+            comparison.different.push(relname);
+        } else if by_content {
+            let mut host_file = fs::File::open(&host_path)?;
+            let mut repo_file = repo.open_file(entry.path())?;
+            if !same_content(&mut repo_file, &mut host_file)? {
+                comparison.different.push(relname);
+            }
+        } else {
+            let secs = |t: ::std::time::SystemTime| t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).ok();
+            if secs(repo_meta.modified()) != secs(host_meta.modified()?) {
+                comparison.different.push(relname);
+            }
+        }
+    }
+
+    for name in host_entries.keys() {
+        comparison.host_only.push(relpath.join(name).to_string_lossy().into_owned());
+    }
+    for name in &skipped {
+        comparison.skipped.push(relpath.join(name).to_string_lossy().into_owned());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::collections::{BTreeMap, HashSet};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+
+/// The recorded state of a single file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: usize,
+    pub version: usize,
+    pub modified: u64,
+    pub sha256: String,
+}
+
+
+/// The authenticated content of a manifest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestBody {
+    pub top: String,
+    pub created: u64,
+    pub entries: Vec<ManifestEntry>,
+}
+
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedManifest {
+    manifest: ManifestBody,
+    hmac: String,
+}
+
+
+/// Differences between a manifest and the current state of a repository.
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+
+fn sign(body: &ManifestBody, secret: &[u8]) -> Result<String, String> {
+    let data = ::serde_json::to_vec(body).map_err(|err| err.to_string())?;
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).map_err(|_| "invalid secret".to_string())?;
+    mac.input(&data);
+    Ok(mac.result().code().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+
+/// Serialize a manifest as JSON, authenticated with an HMAC of `secret`.
+pub fn to_json(body: ManifestBody, secret: &[u8]) -> Result<String, String> {
+    let hmac = sign(&body, secret)?;
+    let signed = SignedManifest { manifest: body, hmac };
+    ::serde_json::to_string_pretty(&signed).map_err(|err| err.to_string())
+}
+
+
+/// Parse a JSON manifest, checking it was signed with `secret`.
+pub fn from_json(manifest: &str, secret: &[u8]) -> Result<ManifestBody, String> {
+    let signed: SignedManifest = ::serde_json::from_str(manifest).map_err(|err| err.to_string())?;
+    let expected = sign(&signed.manifest, secret)?;
+
+    // Compare in constant time to avoid leaking the expected signature
+    let matching = expected.len() == signed.hmac.len() && expected
+        .bytes()
+        .zip(signed.hmac.bytes())
+        .fold(0, |acc, (a, b)| acc | (a ^ b)) == 0;
+
+    if matching {
+        Ok(signed.manifest)
+    } else {
+        Err("manifest signature does not match".to_string())
+    }
+}
+
+
+/// Compare a manifest with the entries currently in a repository.
+pub fn diff(body: &ManifestBody, current: &[ManifestEntry]) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+    let recorded: BTreeMap<&str, &ManifestEntry> = body.entries
+        .iter()
+        .map(|e| (e.path.as_str(), e))
+        .collect();
+    let mut seen = HashSet::new();
+
+    for entry in current {
+        seen.insert(entry.path.as_str());
+        match recorded.get(entry.path.as_str()) {
+            None => diff.extra.push(entry.path.clone()),
+            Some(old) if old.size != entry.size || old.sha256 != entry.sha256 => {
+                diff.changed.push(entry.path.clone())
+            }
+            Some(_) => (),
+        }
+    }
+
+    for path in recorded.keys() {
+        if !seen.contains(path) {
+            diff.missing.push(path.to_string());
+        }
+    }
+
+    diff
+}
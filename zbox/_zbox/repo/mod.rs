@@ -1,4 +1,22 @@
+mod archive;
+mod batch;
+mod changes;
+mod checksum;
 mod errors;
+mod git;
+mod grep;
+mod host;
+mod manifest;
+mod options;
 mod repo;
+mod report;
+mod snapshot;
+mod transfer;
+pub mod trash;
+mod usage;
+mod verify;
+pub mod walk;
 
+pub use self::batch::Batch;
+pub use self::grep::Matches;
 pub use self::repo::Repo;
@@ -0,0 +1,33 @@
+use pyo3::prelude::*;
+use pyo3::exc;
+
+use zbox::{Cipher, MemLimit, OpsLimit};
+
+
+pub fn parse_cipher(name: &str) -> PyResult<Cipher> {
+    match name.to_lowercase().as_str() {
+        "xchacha" | "xchacha20" | "xchacha20-poly1305" => Ok(Cipher::Xchacha),
+        "aes" | "aes256gcm" | "aes-256-gcm" => Ok(Cipher::Aes),
+        _ => Err(exc::ValueError::new(format!("unknown cipher: {}", name))),
+    }
+}
+
+
+pub fn parse_ops_limit(name: &str) -> PyResult<OpsLimit> {
+    match name.to_lowercase().as_str() {
+        "interactive" => Ok(OpsLimit::Interactive),
+        "moderate" => Ok(OpsLimit::Moderate),
+        "sensitive" => Ok(OpsLimit::Sensitive),
+        _ => Err(exc::ValueError::new(format!("unknown ops limit: {}", name))),
+    }
+}
+
+
+pub fn parse_mem_limit(name: &str) -> PyResult<MemLimit> {
+    match name.to_lowercase().as_str() {
+        "interactive" => Ok(MemLimit::Interactive),
+        "moderate" => Ok(MemLimit::Moderate),
+        "sensitive" => Ok(MemLimit::Sensitive),
+        _ => Err(exc::ValueError::new(format!("unknown mem limit: {}", name))),
+    }
+}
@@ -1,5 +1,7 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error as StdError;
-use std::time::UNIX_EPOCH;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use pyo3::prelude::*;
 use pyo3::py::*;
@@ -7,19 +9,67 @@ use pyo3::exc;
 
 use ::file::File;
 use ::file::Mode;
+use ::pyio;
+use ::repo::archive;
+use ::repo::batch::Batch;
+use ::repo::changes;
+use ::repo::checksum::{self, Algorithm};
 use ::repo::errors::Error;
+use ::repo::git;
+use ::repo::grep::Matches;
+use ::repo::host;
+use ::repo::manifest::{self, ManifestBody, ManifestEntry};
+use ::repo::options;
+use ::repo::report::{Changes, Report, SyncReport};
+use ::repo::snapshot::Snapshot;
+use ::repo::transfer;
+use ::repo::trash;
+use ::repo::usage;
+use ::repo::verify;
+use ::repo::walk;
 
 
 #[class(subclass)]
 pub struct Repo {
     repo: ::zbox::Repo,
+    trash: bool,
+    checksums: HashMap<(String, SystemTime, usize, Algorithm), String>,
     token: PyToken,
 }
 
 
 impl Repo {
     pub fn new(token: PyToken, repo: ::zbox::Repo) -> Self {
-        Self { token, repo }
+        Self { token, repo, trash: false, checksums: HashMap::new() }
+    }
+
+    /// Move an entry to the trash instead of removing it.
+    fn discard(&mut self, path: &str) -> PyResult<()> {
+        trash::move_to_trash(&mut self.repo, Path::new(path))
+            .map(|_| ())
+            .map_err(|err| Error::from(err).into())
+    }
+
+    pub fn inner(&mut self) -> &mut ::zbox::Repo {
+        &mut self.repo
+    }
+
+    pub fn open_with_mode(&mut self, path: &str, mode: &str) -> PyResult<Py<File>> {
+        let _mode = Mode::from(mode);
+        match ::zbox::OpenOptions::new()
+            .read(_mode.reading)
+            .write(_mode.writing)
+            .append(_mode.appending)
+            .create(_mode.create)
+            .create_new(_mode.exclusive)
+            .truncate(_mode.truncate)
+            .open(&mut self.repo, path)
+        {
+            Ok(file) => self.token
+                .py()
+                .init(|token| File::new(token, file, _mode)),
+            Err(err) => Error::from(err).into(),
+        }
     }
 
     // FIXME: no unwrap if possible !
@@ -62,6 +112,155 @@ impl Repo {
 
         entrydict
     }
+
+    pub fn dict_from_report(&self, report: &Report) -> PyResult<&PyDict> {
+        let reportdict = PyDict::new(self.token.py());
+        reportdict.set_item("copied", &report.copied)?;
+        reportdict.set_item("skipped", &report.skipped)?;
+        Ok(reportdict)
+    }
+
+    pub fn dict_from_sync_report(&self, report: &SyncReport) -> PyResult<&PyDict> {
+        let reportdict = PyDict::new(self.token.py());
+        reportdict.set_item("added", &report.added)?;
+        reportdict.set_item("updated", &report.updated)?;
+        reportdict.set_item("deleted", &report.deleted)?;
+        Ok(reportdict)
+    }
+
+    fn checksum_impl(
+        &mut self,
+        path: &str,
+        algo: Algorithm,
+        version: Option<usize>,
+        cache: bool,
+    ) -> PyResult<String> {
+        // Versions are immutable, so a version of a file always has the same
+        // checksum; the creation time tells apart files recreated at the same path
+        let meta = self.repo.metadata(path).map_err(Error::from)?;
+        let num = version.unwrap_or(meta.curr_version());
+        let key = (path.to_owned(), meta.created(), num, algo);
+        if let Some(digest) = self.checksums.get(&key) {
+            return Ok(digest.clone());
+        }
+
+        // The opened file does not borrow the repository, so it can be hashed
+        // while other threads run
+        let file = self.repo.open_file(path).map_err(Error::from)?;
+        let digest = self.token
+            .py()
+            .allow_threads(move || checksum::checksum(file, algo, version))
+            .map_err(Error::from)?;
+
+        if cache {
+            self.checksums.insert(key, digest.clone());
+        }
+        Ok(digest)
+    }
+
+    fn manifest_entries(&mut self, top: &str) -> PyResult<Vec<ManifestEntry>> {
+        let files = walk::walk_files(&self.repo, Path::new(top)).map_err(Error::from)?;
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let meta = file.metadata();
+            let path = file.path().to_string_lossy().into_owned();
+            entries.push(ManifestEntry {
+                sha256: self.checksum_impl(&path, Algorithm::Sha256, None, true)?,
+                size: meta.len(),
+                version: meta.curr_version(),
+                modified: meta.modified().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+                path,
+            });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    /// Copy `src` to `dst`, never descending into `top`, the destination of
+    /// the whole copy, which may be anywhere inside of `src`.
+    fn copytree_impl(
+        &mut self,
+        src: &Path,
+        dst: &Path,
+        top: &Path,
+        dirs_exist_ok: bool,
+        ignore: &Option<PyObject>,
+    ) -> PyResult<()> {
+        use ::zbox::Error::AlreadyExists;
+        let py = self.token.py();
+
+        let mut entries = self.repo.read_dir(src).map_err(Error::from)?;
+        entries.retain(|e| !walk::is_internal(e.path()));
+        match self.repo.create_dir(dst) {
+            Ok(()) => (),
+            Err(AlreadyExists) if dirs_exist_ok && self.repo.is_dir(dst) => (),
+            Err(err) => return Error::from(err).into(),
+        }
+
+        let names: Vec<String> = entries.iter().map(|e| e.file_name().to_owned()).collect();
+
+        // Ask the `ignore` callable which names should be skipped, like `shutil.copytree`
+        let mut ignored = HashSet::new();
+        if let Some(ref callback) = *ignore {
+            let dirname = src.to_string_lossy().into_owned();
+            let result = callback.call(py, (dirname, names.clone()), None)?;
+            for name in result.as_ref(py).iter()? {
+                ignored.insert(name?.extract::<String>()?);
+            }
+        }
+
+        for (entry, name) in entries.iter().zip(names) {
+            let target = dst.join(&name);
+            if ignored.contains(&name) || entry.path() == top || walk::is_internal(&target) {
+                continue;
+            }
+            if entry.metadata().is_dir() {
+                self.copytree_impl(entry.path(), &target, top, dirs_exist_ok, ignore)?;
+            } else {
+                self.repo.copy(entry.path(), &target).map_err(Error::from)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rmtree_impl(&mut self, path: &Path, onerror: &PyObject) -> PyResult<()> {
+        let (result, function) = match self.repo.read_dir(path) {
+            Err(err) => (Err(err), "read_dir"),
+            Ok(entries) => {
+                for entry in entries.iter() {
+                    if entry.metadata().is_dir() {
+                        self.rmtree_impl(entry.path(), onerror)?;
+                    } else if let Err(err) = self.repo.remove_file(entry.path()) {
+                        self.report_error(onerror, "remove_file", entry.path(), err)?;
+                    }
+                }
+                (self.repo.remove_dir(path), "remove_dir")
+            }
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => self.report_error(onerror, function, path, err),
+        }
+    }
+
+    /// Call `onerror(function, path, excinfo)` the same way `shutil.rmtree` does.
+    fn report_error(
+        &self,
+        onerror: &PyObject,
+        function: &str,
+        path: &Path,
+        err: ::zbox::Error,
+    ) -> PyResult<()> {
+        let py = self.token.py();
+        let mut pyerr = PyErr::from(Error::from(err));
+        let value = pyerr.instance(py);
+        let excinfo = (value.as_ref(py).get_type(), value.clone_ref(py), py.None());
+        let func = self.to_object(py).getattr(py, function)?;
+        onerror.call(py, (func, path.to_string_lossy().into_owned(), excinfo), None)?;
+        Ok(())
+    }
 }
 
 
@@ -70,14 +269,25 @@ impl Repo {
     // FIXME: allow any object instead of only &str as Path
 
     #[new]
-    #[args(create = "true")]
-    fn __new__(obj: &PyRawObject, uri: &str, pwd: &str, create: bool) -> PyResult<()> {
+    #[args(create = "true", trash = "false")]
+    fn __new__(obj: &PyRawObject, uri: &str, pwd: &str, create: bool, trash: bool) -> PyResult<()> {
         match ::zbox::RepoOpener::new().create(create).open(uri, pwd) {
-            Ok(repo) => obj.init(|token| Repo { repo, token }),
+            Ok(repo) => obj.init(|token| Repo { repo, trash, checksums: HashMap::new(), token }),
             Err(err) => Error::from(err).into(),
         }
     }
 
+    #[getter]
+    fn trash(&self) -> PyResult<bool> {
+        Ok(self.trash)
+    }
+
+    #[setter]
+    fn set_trash(&mut self, trash: bool) -> PyResult<()> {
+        self.trash = trash;
+        Ok(())
+    }
+
     #[classmethod]
     fn exists(_cls: &PyType, uri: &str) -> PyResult<bool> {
         ::zbox::Repo::exists(uri).map_err(|e| Error::from(e).into())
@@ -97,21 +307,7 @@ impl Repo {
 
     #[args(mode = "\"r\"")]
     fn open(&mut self, path: &str, mode: &str) -> PyResult<Py<File>> {
-        let _mode = Mode::from(mode);
-        match ::zbox::OpenOptions::new()
-            .read(_mode.reading)
-            .write(_mode.writing)
-            .append(_mode.appending)
-            .create(_mode.create)
-            .create_new(_mode.exclusive)
-            .truncate(_mode.truncate)
-            .open(&mut self.repo, path)
-        {
-            Ok(file) => self.token
-                .py()
-                .init(|token| File::new(token, file, _mode)),
-            Err(err) => Error::from(err).into(),
-        }
+        self.open_with_mode(path, mode)
     }
 
     // fn create_file(&mut self, path: &str) -> PyResult<File> {
@@ -137,6 +333,7 @@ impl Repo {
             Err(err) => Error::from(err).into(),
             Ok(entries) => Ok(entries
                 .iter()
+                .filter(|e| !walk::is_internal(e.path()))
                 .map(|ref e| self.dict_from_direntry(e))
                 .collect()),
         }
@@ -158,18 +355,27 @@ impl Repo {
     }
 
     fn remove_file(&mut self, path: &str) -> PyResult<()> {
+        if self.trash && self.repo.is_file(path) {
+            return self.discard(path);
+        }
         self.repo
             .remove_file(path)
             .map_err(|err| Error::from(err).into())
     }
 
     fn remove_dir(&mut self, path: &str) -> PyResult<()> {
+        if self.trash && self.repo.read_dir(path).map(|e| e.is_empty()).unwrap_or(false) {
+            return self.discard(path);
+        }
         self.repo
             .remove_dir(path)
             .map_err(|err| Error::from(err).into())
     }
 
     fn remove_dir_all(&mut self, path: &str) -> PyResult<()> {
+        if self.trash && self.repo.is_dir(path) {
+            return self.discard(path);
+        }
         self.repo
             .remove_dir_all(path)
             .map_err(|err| Error::from(err).into())
@@ -180,4 +386,553 @@ impl Repo {
             .rename(from, to)
             .map_err(|err| Error::from(err).into())
     }
+
+    #[args(dirs_exist_ok = "false")]
+    fn copytree(
+        &mut self,
+        src: &str,
+        dst: &str,
+        dirs_exist_ok: bool,
+        ignore: Option<PyObject>,
+    ) -> PyResult<String> {
+        if !self.repo.is_dir(src) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        self.copytree_impl(Path::new(src), Path::new(dst), Path::new(dst), dirs_exist_ok, &ignore)?;
+        Ok(dst.to_owned())
+    }
+
+    // NB: exposed as `Repo.move` by `__getattr__` (`move` is a Rust keyword),
+    // so only instances have it: `hasattr(Repo, 'move')` is False and
+    // `help(Repo)` lists it as `move_`
+    fn move_(&mut self, src: &str, dst: &str) -> PyResult<String> {
+        use ::zbox::Error::{AlreadyExists, InvalidArgument, NotFound};
+
+        if !self.repo.path_exists(src) {
+            return Error::from(NotFound).into();
+        }
+
+        // Moving into an existing directory puts `src` inside of it
+        let mut target = PathBuf::from(dst);
+        if self.repo.is_dir(dst) {
+            let name = Path::new(src).file_name().ok_or(Error::from(InvalidArgument))?;
+            target.push(name);
+            if self.repo.path_exists(&target) {
+                return Error::from(AlreadyExists).into();
+            }
+        }
+
+        // Refuse to move a directory inside itself
+        if self.repo.is_dir(src) && target.starts_with(src) {
+            return Error::from(InvalidArgument).into();
+        }
+
+        // Moving onto itself is a no-op, and must not remove the destination
+        if target.as_path() == Path::new(src) {
+            return Ok(target.to_string_lossy().into_owned());
+        }
+
+        // Overwrite an existing file destination
+        if self.repo.is_file(&target) && self.repo.is_file(src) {
+            trash::remove(&mut self.repo, &target, self.trash).map_err(Error::from)?;
+        }
+
+        self.repo.rename(src, &target).map_err(Error::from)?;
+        Ok(target.to_string_lossy().into_owned())
+    }
+
+    fn rmtree(&mut self, path: &str, onerror: Option<PyObject>) -> PyResult<()> {
+        match onerror {
+            None => self.remove_dir_all(path),
+            // The whole tree goes to the trash at once, so it is restored as one
+            Some(ref callback) if self.trash && self.repo.is_dir(path) => {
+                match trash::move_to_trash(&mut self.repo, Path::new(path)) {
+                    Ok(_) => Ok(()),
+                    Err(err) => self.report_error(callback, "remove_dir_all", Path::new(path), err),
+                }
+            }
+            Some(ref callback) => self.rmtree_impl(Path::new(path), callback),
+        }
+    }
+
+    #[args(recursive = "true")]
+    fn copy_to(&mut self, other: &mut Repo, src: &str, dst: &str, recursive: bool) -> PyResult<()> {
+        if self as *const Repo == other as *const Repo {
+            return if recursive {
+                self.copytree(src, dst, true, None).map(|_| ())
+            } else {
+                self.copy(src, dst)
+            };
+        }
+        if !recursive && self.repo.is_dir(src) {
+            return Error::from(::zbox::Error::IsDir).into();
+        }
+        transfer::copy_tree(&mut self.repo, Path::new(src), &mut other.repo, Path::new(dst), false)
+            .map_err(|err| Error::from(err).into())
+    }
+
+    #[args(recursive = "true")]
+    fn move_to(&mut self, other: &mut Repo, src: &str, dst: &str, recursive: bool) -> PyResult<()> {
+        if self as *const Repo == other as *const Repo {
+            return self.move_(src, dst).map(|_| ());
+        }
+        self.copy_to(other, src, dst, recursive)?;
+        let removed = if self.repo.is_dir(src) {
+            self.repo.remove_dir_all(src)
+        } else {
+            self.repo.remove_file(src)
+        };
+        removed.map_err(|err| Error::from(err).into())
+    }
+
+    #[args(follow_symlinks = "false")]
+    fn import_tree(
+        &mut self,
+        host_dir: &str,
+        repo_dir: &str,
+        follow_symlinks: bool,
+        exclude: Option<Vec<&str>>,
+    ) -> PyResult<&PyDict> {
+        let mut patterns = Vec::new();
+        for pattern in exclude.unwrap_or_default() {
+            match ::glob::Pattern::new(pattern) {
+                Ok(p) => patterns.push(p),
+                Err(err) => return Err(exc::ValueError::new(format!("{}: {}", pattern, err))),
+            }
+        }
+
+        let opts = host::ImportOptions { follow_symlinks, exclude: &patterns };
+        let report = host::import_tree(
+            &mut self.repo,
+            Path::new(host_dir),
+            Path::new(repo_dir),
+            opts,
+        ).map_err(Error::from)?;
+
+        self.dict_from_report(&report)
+    }
+
+    #[args(overwrite = "false")]
+    fn export_tree(&mut self, repo_dir: &str, host_dir: &str, overwrite: bool) -> PyResult<&PyDict> {
+        if !self.repo.is_dir(repo_dir) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        let report = host::export_tree(
+            &mut self.repo,
+            Path::new(repo_dir),
+            Path::new(host_dir),
+            overwrite,
+        ).map_err(Error::from)?;
+
+        self.dict_from_report(&report)
+    }
+
+    #[args(delete = "false", checksum = "false", dry_run = "false")]
+    fn sync_from_host(
+        &mut self,
+        host_dir: &str,
+        repo_dir: &str,
+        delete: bool,
+        checksum: bool,
+        dry_run: bool,
+    ) -> PyResult<&PyDict> {
+        let opts = host::SyncOptions { delete, checksum, dry_run, trash: self.trash };
+        let report = host::sync_from_host(
+            &mut self.repo,
+            Path::new(host_dir),
+            Path::new(repo_dir),
+            opts,
+        ).map_err(Error::from)?;
+
+        let reportdict = self.dict_from_sync_report(&report)?;
+        reportdict.set_item("skipped", &report.skipped)?;
+        Ok(reportdict)
+    }
+
+    #[args(by = "\"size_mtime\"")]
+    fn compare_with_host(&mut self, repo_dir: &str, host_dir: &str, by: &str) -> PyResult<&PyDict> {
+        let by_content = match by {
+            "content" => true,
+            "size_mtime" => false,
+            _ => return Err(exc::ValueError::new(format!("unsupported comparison: {}", by))),
+        };
+        let comparison = host::compare(
+            &mut self.repo,
+            Path::new(repo_dir),
+            Path::new(host_dir),
+            by_content,
+        ).map_err(Error::from)?;
+
+        let comparisondict = PyDict::new(self.token.py());
+        comparisondict.set_item("repo_only", &comparison.repo_only)?;
+        comparisondict.set_item("host_only", &comparison.host_only)?;
+        comparisondict.set_item("different", &comparison.different)?;
+        comparisondict.set_item("skipped", &comparison.skipped)?;
+        Ok(comparisondict)
+    }
+
+    #[args(src_dir = "\"/\"", dst_dir = "\"/\"", delete = "false")]
+    fn sync_to(
+        &mut self,
+        other: &mut Repo,
+        src_dir: &str,
+        dst_dir: &str,
+        delete: bool,
+    ) -> PyResult<&PyDict> {
+        if self as *const Repo == other as *const Repo {
+            return Err(exc::ValueError::new("can not synchronise a repository with itself"));
+        }
+        // Entries of the replica are trashed according to the replica's own mode
+        let opts = transfer::ReplicaOptions { delete, trash: other.trash };
+        let report = transfer::sync_tree(
+            &mut self.repo,
+            Path::new(src_dir),
+            &mut other.repo,
+            Path::new(dst_dir),
+            opts,
+        ).map_err(Error::from)?;
+
+        self.dict_from_sync_report(&report)
+    }
+
+    /// Copy `src_dir` into a new repository at `uri`, and return it opened.
+    ///
+    /// If copying fails, the partially populated repository stays at `uri`,
+    /// and cloning to it again fails: open it with `create=False` instead.
+    #[args(src_dir = "\"/\"", include_history = "false")]
+    fn clone_to(
+        &mut self,
+        uri: &str,
+        pwd: &str,
+        src_dir: &str,
+        cipher: Option<&str>,
+        compress: Option<bool>,
+        ops_limit: Option<&str>,
+        mem_limit: Option<&str>,
+        include_history: bool,
+    ) -> PyResult<Py<Repo>> {
+        if !self.repo.is_dir(src_dir) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+
+        // Settings that are not given are inherited from this repository
+        let info = self.repo.info();
+        let mut opener = ::zbox::RepoOpener::new();
+        opener
+            .create_new(true)
+            .cipher(cipher.map(options::parse_cipher).unwrap_or(Ok(info.cipher()))?)
+            .ops_limit(ops_limit.map(options::parse_ops_limit).unwrap_or(Ok(info.ops_limit()))?)
+            .mem_limit(mem_limit.map(options::parse_mem_limit).unwrap_or(Ok(info.mem_limit()))?)
+            .compress(compress.unwrap_or(info.compress()))
+            .version_limit(info.version_limit());
+
+        let mut repo = opener.open(uri, pwd).map_err(Error::from)?;
+        transfer::copy_tree(&mut self.repo, Path::new(src_dir), &mut repo, Path::new("/"), include_history)
+            .map_err(Error::from)?;
+
+        self.token.py().init(|token| Repo::new(token, repo))
+    }
+
+    #[args(top = "\"/\"")]
+    fn snapshot(&mut self, top: &str, label: Option<String>) -> PyResult<String> {
+        let label = label.unwrap_or_else(|| {
+            let now = ::std::time::SystemTime::now();
+            let secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            format!("snapshot-{}", secs)
+        });
+
+        let snapshot = Snapshot::capture(&self.repo, top, &label).map_err(Error::from)?;
+        snapshot.save(&mut self.repo).map_err(Error::from)?;
+        Ok(label)
+    }
+
+    fn batch(&self) -> PyResult<Py<Batch>> {
+        let repo: Py<Repo> = self.into();
+        self.token.py().init(|token| Batch::new(token, repo))
+    }
+
+    fn list_snapshots(&self) -> PyResult<Vec<String>> {
+        Snapshot::list(&self.repo).map_err(|err| Error::from(err).into())
+    }
+
+    #[args(dry_run = "false")]
+    fn restore_snapshot(&mut self, label: &str, dry_run: bool) -> PyResult<&PyDict> {
+        let snapshot = Snapshot::load(&mut self.repo, label).map_err(Error::from)?;
+        let report = snapshot.restore(&mut self.repo, dry_run).map_err(Error::from)?;
+
+        let reportdict = PyDict::new(self.token.py());
+        reportdict.set_item("restored", &report.restored)?;
+        reportdict.set_item("removed", &report.removed)?;
+        reportdict.set_item("unrestorable", &report.unrestorable)?;
+        Ok(reportdict)
+    }
+
+    /// List the files changed since a UNIX timestamp or a snapshot label.
+    #[args(top = "\"/\"")]
+    fn changes_since(&mut self, when: &PyObjectRef, top: &str) -> PyResult<&PyDict> {
+        let changes: Changes = if let Ok(label) = when.extract::<String>() {
+            let snapshot = Snapshot::load(&mut self.repo, &label).map_err(Error::from)?;
+            changes::since_snapshot(&self.repo, Path::new(top), &snapshot)
+        } else {
+            let secs = when.extract::<f64>()?;
+            if secs < 0.0 {
+                return Err(exc::ValueError::new("timestamp must be positive"));
+            }
+            let nanos = (secs.fract() * 1e9) as u32;
+            let time = UNIX_EPOCH + Duration::new(secs.trunc() as u64, nanos);
+            changes::since_time(&self.repo, Path::new(top), time)
+        }.map_err(Error::from)?;
+
+        let changesdict = PyDict::new(self.token.py());
+        changesdict.set_item("added", &changes.added)?;
+        changesdict.set_item("modified", &changes.modified)?;
+        changesdict.set_item("removed", &changes.removed)?;
+        Ok(changesdict)
+    }
+
+    #[args(algorithm = "\"sha256\"", cache = "true")]
+    fn checksum(
+        &mut self,
+        path: &str,
+        algorithm: &str,
+        version: Option<usize>,
+        cache: bool,
+    ) -> PyResult<String> {
+        let algo = Algorithm::from_name(algorithm)
+            .ok_or_else(|| exc::ValueError::new(format!("unsupported algorithm: {}", algorithm)))?;
+        self.checksum_impl(path, algo, version, cache)
+    }
+
+    #[args(top = "\"/\"", min_size = "1")]
+    fn find_duplicates(&mut self, top: &str, min_size: usize) -> PyResult<Vec<Vec<String>>> {
+        // Only files sharing the same size can have the same content
+        let mut sizes: HashMap<usize, Vec<String>> = HashMap::new();
+        for entry in walk::walk_files(&self.repo, Path::new(top)).map_err(Error::from)? {
+            let len = entry.metadata().len();
+            if len >= min_size {
+                let path = entry.path().to_string_lossy().into_owned();
+                sizes.entry(len).or_insert_with(Vec::new).push(path);
+            }
+        }
+
+        let mut groups = Vec::new();
+        for (_, paths) in sizes.into_iter().filter(|&(_, ref p)| p.len() > 1) {
+            let mut digests: HashMap<String, Vec<String>> = HashMap::new();
+            for path in paths {
+                let digest = self.checksum_impl(&path, Algorithm::Sha256, None, true)?;
+                digests.entry(digest).or_insert_with(Vec::new).push(path);
+            }
+            groups.extend(digests.into_iter().map(|(_, g)| g).filter(|g| g.len() > 1));
+        }
+
+        for group in groups.iter_mut() {
+            group.sort();
+        }
+        groups.sort();
+        Ok(groups)
+    }
+
+    #[args(path = "\"/\"")]
+    fn du(&self, path: &str) -> PyResult<&PyDict> {
+        if !self.repo.is_dir(path) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        let usage = usage::usage(&self.repo, Path::new(path), false).map_err(Error::from)?;
+
+        let usagedict = PyDict::new(self.token.py());
+        usagedict.set_item("bytes", usage.bytes)?;
+        usagedict.set_item("files", usage.files)?;
+        usagedict.set_item("dirs", usage.dirs)?;
+        Ok(usagedict)
+    }
+
+    fn stats(&self) -> PyResult<&PyDict> {
+        let usage = usage::usage(&self.repo, Path::new("/"), true).map_err(Error::from)?;
+        let storage = usage::storage_size(&self.repo)?;
+
+        let statsdict = PyDict::new(self.token.py());
+        statsdict.set_item("bytes", usage.bytes)?;
+        statsdict.set_item("files", usage.files)?;
+        statsdict.set_item("dirs", usage.dirs)?;
+        statsdict.set_item("versions", usage.versions)?;
+        statsdict.set_item("storage_bytes", storage)?;
+        Ok(statsdict)
+    }
+
+    #[args(top = "\"/\"", include_versions = "false")]
+    fn verify(
+        &mut self,
+        top: &str,
+        include_versions: bool,
+        progress: Option<PyObject>,
+    ) -> PyResult<&PyDict> {
+        let py = self.token.py();
+        let mut problems = Vec::new();
+        let files = verify::collect_files(&self.repo, Path::new(top), &mut problems);
+
+        for (index, path) in files.iter().enumerate() {
+            if let Some(ref callback) = progress {
+                let name = path.to_string_lossy().into_owned();
+                callback.call(py, (name, index, files.len()), None)?;
+            }
+            verify::check_file(&mut self.repo, path, include_versions, &mut problems);
+        }
+
+        let reportdict = PyDict::new(py);
+        let mut errors = Vec::with_capacity(problems.len());
+        for problem in problems {
+            let errordict = PyDict::new(py);
+            errordict.set_item("path", problem.path)?;
+            errordict.set_item("version", problem.version)?;
+            errordict.set_item("error", problem.error)?;
+            errors.push(errordict);
+        }
+        reportdict.set_item("checked", files.len())?;
+        reportdict.set_item("errors", errors)?;
+        Ok(reportdict)
+    }
+
+    #[args(top = "\"/\"", fmt = "\"json\"", secret = "None")]
+    fn export_manifest(&mut self, top: &str, fmt: &str, secret: Option<&str>) -> PyResult<String> {
+        let secret = secret.ok_or_else(|| exc::TypeError::new("export_manifest() requires a secret"))?;
+        if fmt != "json" {
+            return Err(exc::ValueError::new(format!("unsupported manifest format: {}", fmt)));
+        }
+        let body = ManifestBody {
+            top: top.to_owned(),
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            entries: self.manifest_entries(top)?,
+        };
+        manifest::to_json(body, secret.as_bytes()).map_err(exc::ValueError::new)
+    }
+
+    fn verify_manifest(&mut self, manifest: &str, secret: &str) -> PyResult<&PyDict> {
+        let body = manifest::from_json(manifest, secret.as_bytes()).map_err(exc::ValueError::new)?;
+        let current = if self.repo.is_dir(&body.top) {
+            self.manifest_entries(&body.top)?
+        } else {
+            Vec::new()
+        };
+        let diff = manifest::diff(&body, &current);
+
+        let diffdict = PyDict::new(self.token.py());
+        diffdict.set_item("missing", &diff.missing)?;
+        diffdict.set_item("extra", &diff.extra)?;
+        diffdict.set_item("changed", &diff.changed)?;
+        Ok(diffdict)
+    }
+
+    #[args(top = "\"/\"", regex = "true", ignore_case = "false")]
+    fn grep(
+        &self,
+        pattern: &str,
+        top: &str,
+        regex: bool,
+        ignore_case: bool,
+        max_matches: Option<usize>,
+    ) -> PyResult<Py<Matches>> {
+        let escaped;
+        let pattern = if regex {
+            pattern
+        } else {
+            escaped = ::regex::escape(pattern);
+            &escaped
+        };
+        let compiled = ::regex::RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| exc::ValueError::new(err.to_string()))?;
+
+        let files = walk::walk_files(&self.repo, Path::new(top)).map_err(Error::from)?;
+        let paths = files.iter().map(|e| e.path().to_path_buf()).collect();
+
+        let repo: Py<Repo> = self.into();
+        self.token.py().init(|token| Matches::new(token, repo, compiled, paths, max_matches))
+    }
+
+    fn export_tar(&mut self, top: &str, target: &PyObjectRef, compression: Option<&str>) -> PyResult<usize> {
+        let gzip = match compression {
+            None => false,
+            Some("gz") | Some("gzip") => true,
+            Some(other) => return Err(exc::ValueError::new(format!("unsupported compression: {}", other))),
+        };
+        if !self.repo.is_dir(top) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        let writer = pyio::writer(target)?;
+        archive::export_tar(&mut self.repo, Path::new(top), writer, gzip)
+            .map_err(|err| Error::from(err).into())
+    }
+
+    fn import_tar(&mut self, source: &PyObjectRef, dst: &str) -> PyResult<&PyDict> {
+        let reader = pyio::reader(source)?;
+        let report = archive::import_tar(&mut self.repo, reader, Path::new(dst)).map_err(Error::from)?;
+        self.dict_from_report(&report)
+    }
+
+    #[args(compression = "\"deflate\"")]
+    fn export_zip(&mut self, top: &str, target: &PyObjectRef, compression: &str) -> PyResult<usize> {
+        let method = match compression {
+            "deflate" | "deflated" => ::zip::CompressionMethod::Deflated,
+            "store" | "stored" => ::zip::CompressionMethod::Stored,
+            other => return Err(exc::ValueError::new(format!("unsupported compression: {}", other))),
+        };
+        if !self.repo.is_dir(top) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        let writer = pyio::writer(target)?;
+        archive::export_zip(&mut self.repo, Path::new(top), writer, method)
+            .map_err(|err| Error::from(err).into())
+    }
+
+    fn import_zip(&mut self, source: &PyObjectRef, dst: &str) -> PyResult<&PyDict> {
+        let reader = pyio::reader(source)?;
+        let report = archive::import_zip(&mut self.repo, reader, Path::new(dst)).map_err(Error::from)?;
+        self.dict_from_report(&report)
+    }
+
+    fn export_git_fast_import(&mut self, top: &str, out: &PyObjectRef) -> PyResult<usize> {
+        if !self.repo.is_dir(top) {
+            return Error::from(::zbox::Error::NotDir).into();
+        }
+        let mut writer = pyio::writer(out)?;
+        git::export_fast_import(&mut self.repo, Path::new(top), &mut writer)
+            .map_err(|err| Error::from(err).into())
+    }
+
+    fn trash_list(&mut self) -> PyResult<Vec<&PyDict>> {
+        let entries = trash::list(&mut self.repo).map_err(Error::from)?;
+        let mut dicts = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let entrydict = PyDict::new(self.token.py());
+            entrydict.set_item("id", entry.id)?;
+            entrydict.set_item("path", entry.path)?;
+            entrydict.set_item("deleted", entry.deleted)?;
+            dicts.push(entrydict);
+        }
+        Ok(dicts)
+    }
+
+    fn trash_restore(&mut self, id: &str) -> PyResult<String> {
+        trash::restore(&mut self.repo, id).map_err(|err| Error::from(err).into())
+    }
+
+    fn trash_empty(&mut self, older_than: Option<u64>) -> PyResult<usize> {
+        trash::empty(&mut self.repo, older_than).map_err(|err| Error::from(err).into())
+    }
+}
+
+
+#[proto]
+impl<'p> PyObjectProtocol<'p> for Repo {
+
+    // `move` is a Rust keyword, so `Repo.move` is looked up here instead;
+    // being resolved on instances only, it is missing from the class itself
+    fn __getattr__(&self, name: &str) -> PyResult<PyObject> {
+        let py = self.token.py();
+        match name {
+            "move" => self.to_object(py).getattr(py, "move_"),
+            _ => Err(exc::AttributeError::new(name.to_owned())),
+        }
+    }
 }
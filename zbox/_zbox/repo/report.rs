@@ -0,0 +1,40 @@
+/// The outcome of a transfer between the host filesystem and a repository.
+///
+/// Entries are listed by their repository path whichever way the transfer
+/// went, except for archive members that do not map to any repository path.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub copied: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+
+/// The outcome of a synchronisation of a repository directory.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    /// Source entries that could not be read, such as dangling links.
+    pub skipped: Vec<String>,
+}
+
+
+/// The files that changed in a tree since a point in time.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+
+/// Differences between a repository tree and a host tree, as relative paths.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    pub repo_only: Vec<String>,
+    pub host_only: Vec<String>,
+    pub different: Vec<String>,
+    /// Host entries that could not be read, such as dangling links.
+    pub skipped: Vec<String>,
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zbox::Error;
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::walk;
+
+
+/// The directory where snapshot manifests are stored.
+pub const SNAPSHOT_DIR: &str = "/.zbox/snapshots";
+
+
+/// A point-in-time record of the current version of every file in a tree.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub label: String,
+    pub top: String,
+    pub created: u64,
+    pub files: BTreeMap<String, usize>,
+}
+
+
+/// The outcome of restoring a snapshot.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<String>,
+    pub removed: Vec<String>,
+    pub unrestorable: Vec<String>,
+}
+
+
+impl Snapshot {
+    /// Record the current version of every file below `top`.
+    pub fn capture(repo: &Repo, top: &str, label: &str) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for entry in walk::walk_files(repo, Path::new(top))? {
+            let path = entry.path().to_string_lossy().into_owned();
+            files.insert(path, entry.metadata().curr_version());
+        }
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Ok(Snapshot { label: label.to_owned(), top: top.to_owned(), created, files })
+    }
+
+    /// Get the path of a manifest, checking the label can not escape the
+    /// snapshot directory.
+    fn manifest_path(label: &str) -> Result<PathBuf> {
+        if label.is_empty() || label.contains('/') || label == "." || label == ".." {
+            Err(Error::InvalidArgument)
+        } else {
+            Ok(Path::new(SNAPSHOT_DIR).join(label))
+        }
+    }
+
+    /// Store the manifest of this snapshot inside the repository.
+    pub fn save(&self, repo: &mut Repo) -> Result<()> {
+        let path = Self::manifest_path(&self.label)?;
+        if repo.path_exists(&path) {
+            return Err(Error::AlreadyExists);
+        } else if !repo.is_dir(SNAPSHOT_DIR) {
+            repo.create_dir_all(SNAPSHOT_DIR)?;
+        }
+
+        let mut file = repo.create_file(&path)?;
+        file.write_all(&::serde_json::to_vec(self).map_err(io::Error::from)?)?;
+        file.finish()
+    }
+
+    /// Load a snapshot manifest previously stored in the repository.
+    pub fn load(repo: &mut Repo, label: &str) -> Result<Self> {
+        let file = repo.open_file(Self::manifest_path(label)?)?;
+        ::serde_json::from_reader(file).map_err(|_| Error::Corrupted)
+    }
+
+    /// List the labels of the snapshots stored in the repository.
+    pub fn list(repo: &Repo) -> Result<Vec<String>> {
+        if !repo.is_dir(SNAPSHOT_DIR) {
+            return Ok(Vec::new());
+        }
+        let mut labels: Vec<String> = repo
+            .read_dir(SNAPSHOT_DIR)?
+            .iter()
+            .map(|e| e.file_name().to_owned())
+            .collect();
+        labels.sort();
+        Ok(labels)
+    }
+
+    /// Revert every file of the snapshot to its recorded version.
+    ///
+    /// Files created after the snapshot are removed, and files whose recorded
+    /// version is not retained anymore are reported as unrestorable.
+    pub fn restore(&self, repo: &mut Repo, dry_run: bool) -> Result<RestoreReport> {
+        let mut report = RestoreReport::default();
+
+        if repo.is_dir(&self.top) {
+            for entry in walk::walk_files(repo, Path::new(&self.top))? {
+                let path = entry.path().to_string_lossy().into_owned();
+                if !self.files.contains_key(&path) {
+                    if !dry_run {
+                        repo.remove_file(entry.path())?;
+                    }
+                    report.removed.push(path);
+                }
+            }
+        }
+
+        for (path, &version) in self.files.iter() {
+            if !repo.is_file(path) {
+                report.unrestorable.push(path.clone());
+                continue;
+            }
+            if repo.metadata(path)?.curr_version() == version {
+                continue;
+            }
+            if !repo.history(path)?.iter().any(|v| v.num() == version) {
+                report.unrestorable.push(path.clone());
+                continue;
+            }
+            if !dry_run {
+                restore_version(repo, Path::new(path), version)?;
+            }
+            report.restored.push(path.clone());
+        }
+
+        Ok(report)
+    }
+}
+
+
+/// Write the content of an older version of a file as its newest version.
+pub fn restore_version(repo: &mut Repo, path: &Path, version: usize) -> Result<()> {
+    let mut content = Vec::new();
+    repo.open_file(path)?.version_reader(version)?.read_to_end(&mut content)?;
+    let mut file = repo.create_file(path)?;
+    file.write_all(&content)?;
+    file.finish()
+}
@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use zbox::Error;
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::checksum::Algorithm;
+use ::repo::report::SyncReport;
+use ::repo::trash;
+use ::repo::walk::is_internal;
+
+
+/// The directory where synchronisations record the state of what they copied.
+pub const SYNC_DIR: &str = "/.zbox/sync";
+
+
+/// Stream the content of a file from a repository to another.
+pub fn copy_file(src_repo: &mut Repo, src: &Path, dst_repo: &mut Repo, dst: &Path) -> Result<u64> {
+    let mut reader = src_repo.open_file(src)?;
+    let mut writer = dst_repo.create_file(dst)?;
+    let copied = io::copy(&mut reader, &mut writer)?;
+    writer.finish()?;
+    Ok(copied)
+}
+
+
+/// Replay every retained version of a file, oldest first, into another repository.
+pub fn copy_file_history(src_repo: &mut Repo, src: &Path, dst_repo: &mut Repo, dst: &Path) -> Result<()> {
+    let file = src_repo.open_file(src)?;
+    let mut versions = src_repo.history(src)?;
+    versions.sort_by_key(|v| v.num());
+
+    for version in versions {
+        let mut reader = file.version_reader(version.num())?;
+        let mut writer = dst_repo.create_file(dst)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.finish()?;
+    }
+
+    Ok(())
+}
+
+
+/// Recursively copy a file or a directory from a repository to another.
+///
+/// With `history`, every retained version of each file is copied instead of
+/// only the current content.
+pub fn copy_tree(
+    src_repo: &mut Repo,
+    src: &Path,
+    dst_repo: &mut Repo,
+    dst: &Path,
+    history: bool,
+) -> Result<()> {
+    if !src_repo.is_dir(src) {
+        return if history {
+            copy_file_history(src_repo, src, dst_repo, dst)
+        } else {
+            copy_file(src_repo, src, dst_repo, dst).map(|_| ())
+        };
+    }
+
+    if !dst_repo.is_dir(dst) {
+        dst_repo.create_dir(dst)?;
+    }
+
+    for entry in src_repo.read_dir(src)? {
+        let target = dst.join(entry.file_name());
+        if !is_internal(entry.path()) && !is_internal(&target) {
+            copy_tree(src_repo, entry.path(), dst_repo, &target, history)?;
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Options controlling how `sync_tree` applies changes to the replica.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplicaOptions {
+    /// Remove replica entries that are missing from the source.
+    pub delete: bool,
+    /// Move replaced and deleted entries to the trash instead of removing them.
+    pub trash: bool,
+}
+
+
+/// The versions replicated by a previous `sync_tree` between the same two trees.
+///
+/// Each replica file is recorded with the source version it was copied from,
+/// and the version this created in the replica, so that a later sync can tell
+/// whether either side changed since.
+#[derive(Debug, Default)]
+struct SyncState {
+    path: PathBuf,
+    recorded: BTreeMap<String, (usize, usize)>,
+    replicated: BTreeMap<String, (usize, usize)>,
+}
+
+impl SyncState {
+    fn load(src_repo: &Repo, src: &Path, dst_repo: &mut Repo, dst: &Path) -> Result<Self> {
+        let key = format!("{}\n{}\n{}", src_repo.info().uri(), src.display(), dst.display());
+        let name = Algorithm::Sha256.hash_reader(&mut key.as_bytes())?;
+        let mut state = SyncState { path: Path::new(SYNC_DIR).join(name), ..Default::default() };
+        if !dst_repo.is_file(&state.path) {
+            return Ok(state);
+        }
+
+        let file = dst_repo.open_file(&state.path)?;
+        state.recorded = ::serde_json::from_reader(file).map_err(|_| Error::Corrupted)?;
+        Ok(state)
+    }
+
+    /// Replace the recorded versions with the ones replicated by this sync.
+    fn save(&self, dst_repo: &mut Repo) -> Result<()> {
+        if !dst_repo.is_dir(SYNC_DIR) {
+            dst_repo.create_dir_all(SYNC_DIR)?;
+        }
+        let mut file = dst_repo.create_file(&self.path)?;
+        file.write_all(&::serde_json::to_vec(&self.replicated).map_err(io::Error::from)?)?;
+        file.finish()
+    }
+}
+
+
+/// Replicate the `src` directory of a repository to the `dst` directory of another.
+///
+/// A file is copied when it is missing from the replica, or when the source or
+/// the replica got a new version since the last sync between the two trees.
+/// Files this sync has no record of are compared by length and modification time.
+pub fn sync_tree(
+    src_repo: &mut Repo,
+    src: &Path,
+    dst_repo: &mut Repo,
+    dst: &Path,
+    opts: ReplicaOptions,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    if !dst_repo.is_dir(dst) {
+        dst_repo.create_dir_all(dst)?;
+    }
+    let mut state = SyncState::load(src_repo, src, dst_repo, dst)?;
+    sync_dir(src_repo, src, dst_repo, dst, opts, &mut state, &mut report)?;
+    state.save(dst_repo)?;
+    Ok(report)
+}
+
+fn sync_dir(
+    src_repo: &mut Repo,
+    src: &Path,
+    dst_repo: &mut Repo,
+    dst: &Path,
+    opts: ReplicaOptions,
+    state: &mut SyncState,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for entry in src_repo.read_dir(src)? {
+        let meta = entry.metadata();
+        let target = dst.join(entry.file_name());
+        if is_internal(entry.path()) || is_internal(&target) {
+            continue;
+        }
+        let target_name = target.to_string_lossy().into_owned();
+        seen.insert(entry.file_name().to_owned());
+
+        if meta.is_dir() {
+            if dst_repo.is_file(&target) {
+                trash::remove(dst_repo, &target, opts.trash)?;
+                report.deleted.push(target_name);
+            }
+            if !dst_repo.is_dir(&target) {
+                dst_repo.create_dir(&target)?;
+            }
+            sync_dir(src_repo, entry.path(), dst_repo, &target, opts, state, report)?;
+            continue;
+        }
+
+        if dst_repo.is_dir(&target) {
+            trash::remove(dst_repo, &target, opts.trash)?;
+            report.deleted.push(target_name.clone());
+        }
+
+        if !dst_repo.is_file(&target) {
+            report.added.push(target_name.clone());
+        } else {
+            let replica = dst_repo.metadata(&target)?;
+            let versions = (meta.curr_version(), replica.curr_version());
+            let unchanged = match state.recorded.get(&target_name) {
+                Some(&recorded) => recorded == versions,
+                None => replica.len() == meta.len() && replica.modified() >= meta.modified(),
+            };
+            if unchanged {
+                state.replicated.insert(target_name, versions);
+                continue;
+            }
+            report.updated.push(target_name.clone());
+        }
+        copy_file(src_repo, entry.path(), dst_repo, &target)?;
+        let versions = (meta.curr_version(), dst_repo.metadata(&target)?.curr_version());
+        state.replicated.insert(target_name, versions);
+    }
+
+    if opts.delete {
+        for entry in dst_repo.read_dir(dst)? {
+            if seen.contains(entry.file_name()) || is_internal(entry.path()) {
+                continue;
+            }
+            trash::remove(dst_repo, entry.path(), opts.trash)?;
+            report.deleted.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+
+    Ok(())
+}
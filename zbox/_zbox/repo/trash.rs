@@ -0,0 +1,147 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zbox::Error;
+use zbox::Repo;
+use zbox::Result;
+
+
+/// The directory where removed entries are kept until the trash is emptied.
+pub const TRASH_DIR: &str = "/.zbox/trash";
+
+
+/// An entry sitting in the trash.
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub id: String,
+    pub path: String,
+    pub deleted: u64,
+}
+
+
+/// What is recorded about an entry when it is moved to the trash.
+#[derive(Debug, Serialize, Deserialize)]
+struct TrashInfo {
+    path: String,
+    deleted: u64,
+}
+
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn data_path(id: &str) -> PathBuf {
+    Path::new(TRASH_DIR).join(id).join("data")
+}
+
+fn info_path(id: &str) -> PathBuf {
+    Path::new(TRASH_DIR).join(id).join("info")
+}
+
+
+/// Remove an entry, moving it to the trash instead when `trash` is set.
+pub fn remove(repo: &mut Repo, path: &Path, trash: bool) -> Result<()> {
+    if trash {
+        move_to_trash(repo, path).map(|_| ())
+    } else if repo.is_dir(path) {
+        repo.remove_dir_all(path)
+    } else {
+        repo.remove_file(path)
+    }
+}
+
+
+/// Move an entry to the trash, returning the identifier of the trash entry.
+pub fn move_to_trash(repo: &mut Repo, path: &Path) -> Result<String> {
+    if !repo.path_exists(path) {
+        return Err(Error::NotFound);
+    } else if path == Path::new("/") {
+        return Err(Error::IsRoot);
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let mut id = nanos.to_string();
+    let mut suffix = 0;
+    while repo.path_exists(Path::new(TRASH_DIR).join(&id)) {
+        suffix += 1;
+        id = format!("{}-{}", nanos, suffix);
+    }
+
+    repo.create_dir_all(Path::new(TRASH_DIR).join(&id))?;
+    let info = TrashInfo { path: path.to_string_lossy().into_owned(), deleted: now() };
+    let mut file = repo.create_file(info_path(&id))?;
+    file.write_all(&::serde_json::to_vec(&info).map_err(io::Error::from)?)?;
+    file.finish()?;
+
+    repo.rename(path, data_path(&id))?;
+    Ok(id)
+}
+
+
+fn load(repo: &mut Repo, id: &str) -> Result<TrashEntry> {
+    let file = repo.open_file(info_path(id))?;
+    let info: TrashInfo = ::serde_json::from_reader(file).map_err(|_| Error::Corrupted)?;
+    if info.path.is_empty() {
+        return Err(Error::Corrupted);
+    }
+    Ok(TrashEntry { id: id.to_owned(), path: info.path, deleted: info.deleted })
+}
+
+
+/// List the entries in the trash, oldest first.
+pub fn list(repo: &mut Repo) -> Result<Vec<TrashEntry>> {
+    if !repo.is_dir(TRASH_DIR) {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for dir in repo.read_dir(TRASH_DIR)? {
+        entries.push(load(repo, dir.file_name())?);
+    }
+    entries.sort_by(|a, b| (a.deleted, &a.id).cmp(&(b.deleted, &b.id)));
+    Ok(entries)
+}
+
+
+/// Move an entry out of the trash back to its original path.
+pub fn restore(repo: &mut Repo, id: &str) -> Result<String> {
+    if id.is_empty() || id.contains('/') {
+        return Err(Error::InvalidArgument);
+    }
+    let entry = load(repo, id)?;
+    let path = Path::new(&entry.path);
+
+    if repo.path_exists(path) {
+        return Err(Error::AlreadyExists);
+    } else if let Some(parent) = path.parent() {
+        repo.create_dir_all(parent)?;
+    }
+
+    repo.rename(data_path(id), path)?;
+    repo.remove_dir_all(Path::new(TRASH_DIR).join(id))?;
+    Ok(entry.path)
+}
+
+
+/// Permanently delete entries from the trash, returning how many were deleted.
+///
+/// With `older_than`, only entries removed more than that many seconds ago
+/// are deleted.
+pub fn empty(repo: &mut Repo, older_than: Option<u64>) -> Result<usize> {
+    let limit = older_than.map(|age| now().saturating_sub(age));
+    let mut count = 0;
+    for entry in list(repo)? {
+        if limit.map(|l| entry.deleted < l).unwrap_or(true) {
+            repo.remove_dir_all(Path::new(TRASH_DIR).join(&entry.id))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
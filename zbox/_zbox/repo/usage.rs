@@ -0,0 +1,63 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use zbox::Repo;
+use zbox::Result;
+
+use ::repo::walk;
+
+
+/// Space and entry counts of a tree.
+#[derive(Debug, Default)]
+pub struct Usage {
+    pub bytes: u64,
+    pub files: usize,
+    pub dirs: usize,
+    pub versions: usize,
+}
+
+
+/// Compute the logical size and the number of entries below `top`.
+///
+/// Counting retained versions requires reading the history of every file,
+/// and is only done when `versions` is set.
+pub fn usage(repo: &Repo, top: &Path, versions: bool) -> Result<Usage> {
+    let mut usage = Usage::default();
+    for entry in walk::walk(repo, top)? {
+        let meta = entry.metadata();
+        if meta.is_dir() {
+            usage.dirs += 1;
+        } else {
+            usage.files += 1;
+            usage.bytes += meta.len() as u64;
+            if versions {
+                usage.versions += repo.history(entry.path())?.len();
+            }
+        }
+    }
+    Ok(usage)
+}
+
+
+/// Compute the size of the backing storage of a `file://` repository.
+pub fn storage_size(repo: &Repo) -> io::Result<Option<u64>> {
+    let info = repo.info();
+    if info.uri().starts_with("file://") {
+        host_size(Path::new(&info.uri()["file://".len()..])).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn host_size(path: &Path) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += host_size(&entry?.path())?;
+    }
+    Ok(size)
+}
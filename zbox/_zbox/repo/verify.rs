@@ -0,0 +1,78 @@
+use std::error::Error as StdError;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use zbox::Repo;
+
+
+/// An entry that could not be read back from the repository.
+#[derive(Debug)]
+pub struct Problem {
+    pub path: String,
+    pub version: Option<usize>,
+    pub error: String,
+}
+
+impl Problem {
+    fn new<E: StdError>(path: &Path, version: Option<usize>, err: E) -> Self {
+        Problem {
+            path: path.to_string_lossy().into_owned(),
+            version,
+            error: err.to_string(),
+        }
+    }
+}
+
+
+/// List every file below `top`, recording directories that can not be listed.
+pub fn collect_files(repo: &Repo, top: &Path, problems: &mut Vec<Problem>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_into(repo, top, &mut files, problems);
+    files
+}
+
+fn collect_into(repo: &Repo, dir: &Path, files: &mut Vec<PathBuf>, problems: &mut Vec<Problem>) {
+    let entries = match repo.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return problems.push(Problem::new(dir, None, err)),
+    };
+    for entry in entries {
+        if ::repo::walk::is_internal(entry.path()) {
+            continue;
+        } else if entry.metadata().is_dir() {
+            collect_into(repo, entry.path(), files, problems);
+        } else {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+}
+
+
+/// Read a file, and optionally all of its retained versions, to check
+/// their authentication tags.
+pub fn check_file(repo: &mut Repo, path: &Path, versions: bool, problems: &mut Vec<Problem>) {
+    let mut file = match repo.open_file(path) {
+        Ok(file) => file,
+        Err(err) => return problems.push(Problem::new(path, None, err)),
+    };
+    if let Err(err) = io::copy(&mut file, &mut io::sink()) {
+        problems.push(Problem::new(path, None, err));
+    }
+
+    if !versions {
+        return;
+    }
+    let history = match repo.history(path) {
+        Ok(history) => history,
+        Err(err) => return problems.push(Problem::new(path, None, err)),
+    };
+    for version in history {
+        let num = Some(version.num());
+        match file.version_reader(version.num()) {
+            Err(err) => problems.push(Problem::new(path, num, err)),
+            Ok(mut reader) => if let Err(err) = io::copy(&mut reader, &mut io::sink()) {
+                problems.push(Problem::new(path, num, err));
+            },
+        }
+    }
+}
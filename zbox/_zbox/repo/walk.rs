@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use zbox::DirEntry;
+use zbox::Repo;
+use zbox::Result;
+
+
+/// The directory reserved to store bookkeeping data inside a repository.
+///
+/// Batches, snapshots, replicas and the trash each keep their data in a
+/// subdirectory of it, so that no other path of the repository is hidden.
+pub const INTERNAL_DIR: &str = "/.zbox";
+
+
+/// Check whether `path` is, or is inside of, the internal directory.
+pub fn is_internal(path: &Path) -> bool {
+    path.starts_with(INTERNAL_DIR)
+}
+
+
+/// Recursively list every entry below `top`, parents before their children.
+///
+/// Internal directories are never listed.
+pub fn walk(repo: &Repo, top: &Path) -> Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    walk_into(repo, top, &mut entries)?;
+    Ok(entries)
+}
+
+fn walk_into(repo: &Repo, dir: &Path, entries: &mut Vec<DirEntry>) -> Result<()> {
+    for entry in repo.read_dir(dir)? {
+        if is_internal(entry.path()) {
+            continue;
+        }
+        let is_dir = entry.metadata().is_dir();
+        let path = entry.path().to_path_buf();
+        entries.push(entry);
+        if is_dir {
+            walk_into(repo, &path, entries)?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Recursively list every file below `top`.
+pub fn walk_files(repo: &Repo, top: &Path) -> Result<Vec<DirEntry>> {
+    let entries = walk(repo, top)?;
+    Ok(entries.into_iter().filter(|e| e.metadata().is_file()).collect())
+}
@@ -64,3 +64,27 @@ where
         self.seek(::std::io::SeekFrom::Current(0))
     }
 }
+
+/// Compare two readers byte by byte, stopping at the first difference.
+pub fn same_content<A, B>(a: &mut A, b: &mut B) -> ::std::io::Result<bool>
+where
+    A: ::std::io::Read,
+    B: ::std::io::Read,
+{
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        match b.read_exact(&mut buf_b[..read]) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(err) => return Err(err),
+        }
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
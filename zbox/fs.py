@@ -6,6 +6,7 @@ import fs.base
 import fs.errors
 import fs.mode
 from fs.info import Info
+from six.moves.urllib.parse import urlencode
 
 from ._zbox import ZboxFS
 
@@ -24,14 +25,37 @@ class ZboxFS(ZboxFS, fs.base.FS):
         'supports_rename': True,
     }
 
-    def __init__(self, uri, pwd="", create=False):
+    def __init__(self, uri, pwd="", create=True, trash=False):
         super(ZboxFS, self).__init__()
+        self._uri = uri
+        self._url_params = {"trash": "1"} if trash else {}
+
+    def geturl(self, path, purpose="download"):
+        if purpose != "fs":
+            raise fs.errors.NoURL(path, purpose)
+        _path = self.validatepath(path)
+        url = "zbox+{}".format(self._uri)
+        if self._url_params:
+            url += "?" + urlencode(sorted(self._url_params.items()))
+        return "{}!{}".format(url, _path) if _path != "/" else url
+
+    def hasurl(self, path, purpose="download"):
+        return purpose == "fs"
 
     def copy(self, src, dst, overwrite=False):
         _src = self.validatepath(src)
         _dst = self.validatepath(dst)
         return super(ZboxFS, self).copy(_src, _dst, overwrite)
 
+    def copydir(self, src_path, dst_path, create=False):
+        _src = self.validatepath(src_path)
+        _dst = self.validatepath(dst_path)
+        return super(ZboxFS, self).copydir(_src, _dst, create)
+
+    def download(self, path, file, chunk_size=None, progress=None, **options):
+        _path = self.validatepath(path)
+        return super(ZboxFS, self).download(_path, file, chunk_size, progress)
+
     def exists(self, path):
         _path = self.validatepath(path)
         return super(ZboxFS, self).exists(_path)
@@ -55,7 +79,7 @@ class ZboxFS(ZboxFS, fs.base.FS):
 
     def makedir(self, path, permissions=None, recreate=False):
         _path = self.validatepath(path)
-        super(ZboxFS, self).makedir(_path, recreate=recreate)
+        super(ZboxFS, self).makedir(_path, permissions, recreate)
         return self.opendir(_path)
 
     def move(self, src, dst, overwrite=False):
@@ -63,6 +87,11 @@ class ZboxFS(ZboxFS, fs.base.FS):
         _dst = self.validatepath(dst)
         return super(ZboxFS, self).move_(_src, _dst, overwrite)
 
+    def movedir(self, src_path, dst_path, create=False):
+        _src = self.validatepath(src_path)
+        _dst = self.validatepath(dst_path)
+        return super(ZboxFS, self).movedir(_src, _dst, create)
+
     def openbin(self, path, mode="r", buffering=-1, **options):
         _path = self.validatepath(path)
         fs.mode.validate_openbin_mode(mode)
@@ -76,6 +105,23 @@ class ZboxFS(ZboxFS, fs.base.FS):
         _path = self.validatepath(path)
         return super(ZboxFS, self).removedir(_path)
 
+    def removetree(self, dir_path):
+        _path = self.validatepath(dir_path)
+        return super(ZboxFS, self).removetree(_path)
+
+    def scandir(self, path, namespaces=None, page=None):
+        _path = self.validatepath(path)
+        _namespaces = list(namespaces) if namespaces is not None else None
+        rawinfos = super(ZboxFS, self).scandir(_path, _namespaces, page)
+        return iter(Info(rawinfo) for rawinfo in rawinfos)
+
+    def setbinfile(self, path, file):
+        return self.upload(path, file)
+
     def setinfo(self, path, info):
         _path = self.validatepath(path)
         return super(ZboxFS, self).setinfo(_path, info)
+
+    def upload(self, path, file, chunk_size=None, progress=None, **options):
+        _path = self.validatepath(path)
+        return super(ZboxFS, self).upload(_path, file, chunk_size, progress)
@@ -0,0 +1,63 @@
+# coding: utf-8
+from __future__ import absolute_import
+from __future__ import unicode_literals
+
+import io
+import os
+
+from fs.opener import Opener
+from fs.opener.errors import OpenerError
+
+__all__ = ["ZboxOpener"]
+
+
+_TRUE = ("1", "true", "yes", "on")
+
+
+def _read_password(parse_result):
+    """Get the repository password from the URL of a filesystem.
+
+    The password can be given inline with ``?pwd=...``, read from an
+    environment variable with ``?pwd_env=NAME``, read from the first line
+    of a file with ``?pwd_file=PATH``, or given as the URL password.
+    """
+    params = parse_result.params
+    if "pwd" in params:
+        return params["pwd"]
+    if "pwd_env" in params:
+        try:
+            return os.environ[params["pwd_env"]]
+        except KeyError:
+            raise OpenerError("environment variable not set: {}".format(params["pwd_env"]))
+    if "pwd_file" in params:
+        with io.open(params["pwd_file"], encoding="utf-8") as f:
+            return f.readline().rstrip("\r\n")
+    return parse_result.password or ""
+
+
+class ZboxOpener(Opener):
+    """Open a `ZboxFS` from a URL such as ``zbox+file:///data/repo?pwd_env=PWD``.
+
+    The part of the protocol after ``zbox+`` selects the zbox storage backend,
+    and defaults to ``file`` when only ``zbox://`` is given.
+    """
+
+    protocols = ["zbox", "zbox+file", "zbox+mem", "zbox+sqlite", "zbox+redis"]
+
+    def open_fs(self, fs_url, parse_result, writeable, create, cwd):
+        from .fs import ZboxFS
+
+        backend = parse_result.protocol.partition("+")[2] or "file"
+        params = parse_result.params
+        uri = "{}://{}".format(backend, parse_result.resource)
+
+        zbfs = ZboxFS(
+            uri,
+            _read_password(parse_result),
+            create=create or params.get("create", "").lower() in _TRUE,
+            trash=params.get("trash", "").lower() in _TRUE,
+        )
+
+        # Only remember where the password came from, never the password itself
+        zbfs._url_params.update({k: v for k, v in params.items() if k in ("pwd_env", "pwd_file")})
+        return zbfs